@@ -0,0 +1,51 @@
+// Constructs instances of classes with more and more methods. Each instance
+// shares its class, so the cost shouldn't grow with the size of the class.
+
+use criterion::criterion_group;
+use criterion::criterion_main;
+use criterion::BenchmarkId;
+use criterion::Criterion;
+use rlox1::interpreter::Interpreter;
+use rlox1::parser::Parser;
+use rlox1::resolver::Resolver;
+use rlox1::scanner::Scanner;
+use std::fmt::Write;
+use std::io;
+
+const INSTANCES: usize = 1000;
+
+fn script(methods: usize) -> String {
+    let mut src = String::from("class Shape {\n  init(n) { this.n = n; }\n");
+    for i in 0..methods {
+        writeln!(src, "  method{i}() {{ return this.n + {i}; }}")
+            .expect("must be able to append to string");
+    }
+    writeln!(
+        src,
+        "}}\nfor (var i = 0; i < {INSTANCES}; i = i + 1) {{ Shape(i).method0(); }}"
+    )
+    .expect("must be able to append to string");
+    src
+}
+
+fn bench_instances(c: &mut Criterion) {
+    let mut group = c.benchmark_group("instances");
+    for methods in [1, 16, 64] {
+        let src = script(methods);
+        let mut scanner = Scanner::new(&src);
+        let tokens = scanner.scan_tokens().expect("should scan");
+        let stmts = Parser::new(&tokens).parse().expect("should parse");
+        let resolutions = Resolver::default().resolve(&stmts).expect("should resolve");
+        group.bench_with_input(BenchmarkId::new("methods", methods), &stmts, |b, stmts| {
+            b.iter(|| {
+                let mut interpreter = Interpreter::builder().stdout(io::sink()).build();
+                interpreter.resolutions = resolutions.clone();
+                interpreter.interpret(stmts).expect("should run");
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_instances);
+criterion_main!(benches);
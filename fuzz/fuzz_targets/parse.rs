@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rlox1::parser::Parser;
+use rlox1::scanner::Scanner;
+
+// Parses whatever scans, recovering from errors the way tooling does.
+fuzz_target!(|src: &str| {
+    let (tokens, _) = Scanner::new(src).scan_partial();
+    let _ = Parser::new(&tokens).parse_partial();
+});
@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rlox1::parser::Parser;
+use rlox1::resolver::Resolver;
+use rlox1::scanner::Scanner;
+
+// Resolves whatever parses, as the language server does.
+fuzz_target!(|src: &str| {
+    let (tokens, _) = Scanner::new(src).scan_partial();
+    let (stmts, _) = Parser::new(&tokens).parse_partial();
+    let _ = Resolver::default().resolve(&stmts);
+});
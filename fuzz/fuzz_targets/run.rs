@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rlox1::fuzz::check;
+use rlox1::fuzz::generate;
+
+// Arbitrary bytes only rarely make a program that gets as far as running, so
+// this turns them into one, then fails on panics and on backends that
+// disagree.
+fuzz_target!(|data: &[u8]| {
+    let src = generate(data);
+    if let Err(err) = check(&src) {
+        panic!("{err}\n{src}");
+    }
+});
@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rlox1::scanner::Scanner;
+
+fuzz_target!(|src: &str| {
+    let _ = Scanner::new(src).scan_partial();
+});
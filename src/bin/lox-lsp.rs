@@ -0,0 +1,19 @@
+use rlox1::lsp::read_message;
+use rlox1::lsp::write_message;
+use rlox1::lsp::Server;
+use std::io;
+
+fn main() -> io::Result<()> {
+    let mut input = io::stdin().lock();
+    let mut output = io::stdout().lock();
+    let mut server = Server::default();
+    while let Some(message) = read_message(&mut input)? {
+        for reply in server.handle(&message) {
+            write_message(&mut output, &reply)?;
+        }
+        if let Some(code) = server.exit_code {
+            std::process::exit(code);
+        }
+    }
+    Ok(())
+}
@@ -1,45 +1,21 @@
-use crate::class::LoxInstance;
 use crate::environment::Env;
 use crate::environment::Environment;
 use crate::error::RuntimeError;
+use crate::interpreter::Frame;
 use crate::interpreter::Interpreter;
 use crate::value::Value;
 use std::fmt;
 use std::mem;
 use std::rc::Rc;
-use std::time;
 
 pub trait LoxCallable: fmt::Display + fmt::Debug {
+    fn name(&self) -> &str;
     fn arity(&self) -> usize;
     fn call(&self, interpreter: &mut Interpreter, args: Vec<Value>) -> Result<Value, RuntimeError>;
-}
-
-#[derive(Debug)]
-pub struct Clock;
-
-impl fmt::Display for Clock {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "clock")
-    }
-}
 
-impl LoxCallable for Clock {
-    fn arity(&self) -> usize {
-        0
-    }
-
-    fn call(
-        &self,
-        _interpreter: &mut Interpreter,
-        _args: Vec<Value>,
-    ) -> Result<Value, RuntimeError> {
-        let now = time::SystemTime::now();
-        let elapsed =
-            now.duration_since(time::UNIX_EPOCH)
-                .or(Err(RuntimeError::SystemTimeError {
-                    line: "TODO".into(),
-                }))?;
-        Ok(Value::VNumber(elapsed.as_secs_f64()))
+    // Only functions written in Lox can become methods.
+    fn as_function(&self) -> Option<&LoxFunction> {
+        None
     }
 }
 
@@ -49,6 +25,9 @@ pub struct LoxFunction {
     pub definition: Rc<FunDecl>,
     pub closure: Rc<Environment>,
     pub is_init: bool,
+    // A function made into a method by define_method can't say `this`, so
+    // it's handed the instance as its first argument instead.
+    pub takes_this: bool,
 }
 
 impl fmt::Display for LoxFunction {
@@ -58,18 +37,34 @@ impl fmt::Display for LoxFunction {
 }
 
 impl LoxCallable for LoxFunction {
+    fn name(&self) -> &str {
+        &self.definition.name.lexeme
+    }
+
     fn arity(&self) -> usize {
-        self.definition.parameters.len()
+        self.definition.parameters.len() - usize::from(self.takes_this)
     }
 
-    fn call(&self, interpreter: &mut Interpreter, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        mut args: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        if self.takes_this {
+            args.insert(0, self.closure.get_at("this", 0)?);
+        }
+        interpreter.enter(Frame {
+            function: self.definition.name.lexeme.clone(),
+            line: self.definition.line,
+        })?;
         let mut closure = self.closure.push();
         mem::swap(&mut closure, &mut interpreter.environment);
         for (param, arg) in self.definition.parameters.iter().zip(args) {
             interpreter.environment.define(&param.lexeme, arg);
         }
 
-        let result = interpreter.interpret(&self.definition.body);
+        let result = interpreter.execute(&self.definition.body);
+        interpreter.leave();
         mem::swap(&mut closure, &mut interpreter.environment);
         match result {
             Ok(()) => {
@@ -89,16 +84,22 @@ impl LoxCallable for LoxFunction {
             Err(err) => Err(err),
         }
     }
+
+    fn as_function(&self) -> Option<&LoxFunction> {
+        Some(self)
+    }
 }
 
 impl LoxFunction {
-    pub fn bind(&self, instance: Rc<LoxInstance>) -> LoxFunction {
+    // `this` is an instance, or the class itself for static methods.
+    pub fn bind(&self, this: Value) -> LoxFunction {
         let closure = self.closure.push();
 
-        closure.define("this", Value::Object(instance));
+        closure.define("this", this);
         LoxFunction {
             definition: self.definition.clone(),
             is_init: self.is_init,
+            takes_this: self.takes_this,
             closure,
         }
     }
@@ -0,0 +1,89 @@
+use std::path::Path;
+use std::path::PathBuf;
+
+// What natives and `print` may touch on the host. Nothing is granted unless
+// asked for; see Interpreter::builder.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Capabilities {
+    // files at or below these paths may be read
+    pub read: Vec<PathBuf>,
+    // files at or below these paths may be written
+    pub write: Vec<PathBuf>,
+    pub env: bool,
+    pub time: bool,
+    pub stdout: bool,
+}
+
+impl Capabilities {
+    // Everything, for scripts run by their own author.
+    pub fn all() -> Self {
+        Self {
+            read: vec!["/".into()],
+            write: vec!["/".into()],
+            env: true,
+            time: true,
+            stdout: true,
+        }
+    }
+
+    pub fn can_read(&self, path: &Path) -> bool {
+        allowed(&self.read, path)
+    }
+
+    pub fn can_write(&self, path: &Path) -> bool {
+        allowed(&self.write, path)
+    }
+}
+
+// Resolves symlinks and `..` so a path can't escape its allowlist. Files that
+// don't exist yet are resolved through their directory.
+fn resolve(path: &Path) -> Option<PathBuf> {
+    if let Ok(path) = path.canonicalize() {
+        return Some(path);
+    }
+    let name = path.file_name()?;
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    Some(parent.canonicalize().ok()?.join(name))
+}
+
+fn allowed(roots: &[PathBuf], path: &Path) -> bool {
+    let Some(path) = resolve(path) else {
+        return false;
+    };
+    roots
+        .iter()
+        .filter_map(|root| root.canonicalize().ok())
+        .any(|root| path.starts_with(root))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_allowlist() {
+        let root = env::temp_dir().join(format!("rlox1-caps-{}", std::process::id()));
+        let allowed_dir = root.join("allowed");
+        fs::create_dir_all(&allowed_dir).expect("must create test dir");
+        fs::write(root.join("secret"), "x").expect("must create test file");
+
+        let capabilities = Capabilities {
+            read: vec![allowed_dir.clone()],
+            ..Default::default()
+        };
+        assert!(capabilities.can_read(&allowed_dir.join("new_file")));
+        assert!(capabilities.can_read(&allowed_dir));
+        assert!(!capabilities.can_read(&root.join("secret")));
+        assert!(!capabilities.can_read(&allowed_dir.join("../secret")));
+        assert!(!capabilities.can_read(&allowed_dir.join("missing/file")));
+        assert!(!capabilities.can_write(&allowed_dir.join("new_file")));
+        assert!(Capabilities::all().can_write(&root.join("secret")));
+
+        fs::remove_dir_all(&root).expect("must clean up test dir");
+    }
+}
@@ -0,0 +1,870 @@
+use crate::models::Expr;
+use crate::models::FunDecl;
+use crate::models::Stmt;
+use crate::models::StmtList;
+use crate::models::Token;
+use crate::models::TokenType;
+use crate::models::Value;
+use compact_str::CompactString;
+use std::collections::HashMap;
+use std::fmt;
+use std::mem;
+use thiserror::Error;
+
+// A static type. Anything without an annotation is Any, which fits wherever
+// any other type is wanted, so unannotated code is never rejected.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Any,
+    Nil,
+    Bool,
+    Num,
+    Str,
+    // parameter and return types
+    Fun(Vec<Type>, Box<Type>),
+    // a class itself, and instances of it, by name
+    Class(CompactString),
+    Instance(CompactString),
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Any => write!(f, "any"),
+            Type::Nil => write!(f, "nil"),
+            Type::Bool => write!(f, "bool"),
+            Type::Num => write!(f, "num"),
+            Type::Str => write!(f, "str"),
+            Type::Fun(params, ret) => {
+                write!(f, "fun(")?;
+                for (i, param) in params.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{param}")?;
+                }
+                write!(f, "): {ret}")
+            }
+            Type::Class(name) => write!(f, "class {name}"),
+            Type::Instance(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Error)]
+pub enum TypeError {
+    #[error("[line {}] Error at '{}': unknown type", .0.line, .0.lexeme)]
+    UnknownType(Token),
+
+    #[error("[line {line}] Error: {what} must be {want}, got {got}")]
+    Mismatch {
+        line: usize,
+        what: String,
+        want: Type,
+        got: Type,
+    },
+
+    #[error("[line {line}] Error: '{name}' takes {want} arguments, got {got}")]
+    Arity {
+        line: usize,
+        name: CompactString,
+        want: usize,
+        got: usize,
+    },
+
+    #[error("[line {line}] Error: {}", operands(operator, expected, got))]
+    Operands {
+        line: usize,
+        operator: CompactString,
+        expected: &'static str,
+        got: Vec<Type>,
+    },
+
+    #[error("[line {line}] Error: only instances have properties, got {got} for '{name}'")]
+    NotInstance {
+        line: usize,
+        name: CompactString,
+        got: Type,
+    },
+}
+
+// Worded like the runtime error the operands would cause.
+fn operands(operator: &str, expected: &str, got: &[Type]) -> String {
+    let got: Vec<_> = got.iter().map(Type::to_string).collect();
+    match got.len() {
+        1 => format!(
+            "operand of '{operator}' must be a {expected}, got {}",
+            got[0]
+        ),
+        _ => format!(
+            "operands of '{operator}' must be {expected}, got {}",
+            got.join(" and ")
+        ),
+    }
+}
+
+#[derive(Debug, Default)]
+struct ClassInfo {
+    parent: Option<CompactString>,
+    // getters are here as the type they return
+    methods: HashMap<CompactString, Type>,
+    class_methods: HashMap<CompactString, Type>,
+}
+
+// An expression's type, and whether it rests on an annotation. Only those
+// that do are held against the operators they meet, so code without
+// annotations is never rejected, even where it would fail at runtime.
+#[derive(Debug, Clone)]
+struct Typed {
+    ty: Type,
+    declared: bool,
+}
+
+fn inferred(ty: Type) -> Typed {
+    Typed {
+        ty,
+        declared: false,
+    }
+}
+
+// Whether a function's signature has anything to check calls against.
+fn annotated(params: &[Type], ret: &Type) -> bool {
+    params.iter().any(|param| *param != Type::Any) || *ret != Type::Any
+}
+
+// Whether running off the end of the statements is possible, so a function
+// made of them may return nil without saying so.
+fn can_finish(stmts: &StmtList) -> bool {
+    !stmts.into_iter().any(always_returns)
+}
+
+fn always_returns(stmt: &Stmt) -> bool {
+    match stmt {
+        Stmt::Return(..) => true,
+        Stmt::Block(stmts) => !can_finish(stmts),
+        Stmt::IfThenElse {
+            then_stmt,
+            else_stmt: Some(else_stmt),
+            ..
+        } => always_returns(then_stmt) && always_returns(else_stmt),
+        // there's no break, so only a return gets out of `while (true)`
+        Stmt::While(_, Expr::Literal(Value::Bool(true), _), _) => true,
+        _ => false,
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Binding {
+    ty: Type,
+    // whether the type came from an annotation, and so must be kept to
+    annotated: bool,
+}
+
+// Checks annotated code against its annotations, running after the resolver
+// and before the interpreter, which ignores annotations. Types flow from
+// annotations, literals and declarations; there's no flow analysis, so e.g. a
+// function annotated to return a number may still fall off its end.
+//
+// Globals and classes are kept from one check to the next, so a REPL can
+// check each line against the ones before it.
+#[derive(Debug, Default)]
+pub struct Checker {
+    errors: Vec<TypeError>,
+    scopes: Vec<HashMap<CompactString, Binding>>,
+    // by name, so a redefined class replaces the first
+    classes: HashMap<CompactString, ClassInfo>,
+    // the methods of each trait, which a class using it takes on
+    traits: HashMap<CompactString, HashMap<CompactString, Type>>,
+    // the name and declared return type of each function we're inside
+    returns: Vec<(CompactString, Type)>,
+    // what `this` is in the method we're inside: an instance, or the class
+    // itself in a static method
+    this: Option<Type>,
+    // the line of the statement being checked
+    line: usize,
+}
+
+impl Checker {
+    pub fn check(&mut self, stmts: &StmtList) -> Result<(), Vec<TypeError>> {
+        if self.scopes.is_empty() {
+            self.scopes.push(HashMap::new());
+        }
+        self.check_stmts(stmts);
+        // only the globals outlive a check
+        self.scopes.truncate(1);
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(mem::take(&mut self.errors))
+        }
+    }
+
+    fn check_stmts(&mut self, stmts: &StmtList) {
+        // classes can be named in annotations anywhere in their scope, even
+        // above their declaration, e.g. by a function that makes instances
+        for stmt in stmts {
+            if let Stmt::ClassDecl { name, .. } = stmt {
+                self.declare(&name.lexeme, Type::Class(name.lexeme.clone()), false);
+            }
+        }
+        for stmt in stmts {
+            self.check_stmt(stmt);
+        }
+    }
+
+    fn declare(&mut self, name: &str, ty: Type, annotated: bool) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.into(), Binding { ty, annotated });
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Binding> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    fn annotation(&mut self, annotation: &Option<Token>) -> Type {
+        let Some(token) = annotation else {
+            return Type::Any;
+        };
+        match token.lexeme.as_str() {
+            "any" => Type::Any,
+            "nil" => Type::Nil,
+            "bool" => Type::Bool,
+            "num" => Type::Num,
+            "str" => Type::Str,
+            name => match self.lookup(name) {
+                Some(Binding {
+                    ty: Type::Class(class),
+                    ..
+                }) => Type::Instance(class.clone()),
+                _ => {
+                    self.errors.push(TypeError::UnknownType(token.clone()));
+                    Type::Any
+                }
+            },
+        }
+    }
+
+    // Follows a class and its superclasses; redefined classes can make a
+    // cycle of names, so this gives up after visiting every class once.
+    fn ancestors<'a>(&'a self, class: &'a str) -> impl Iterator<Item = (&'a str, &'a ClassInfo)> {
+        let mut next = Some(class);
+        (0..=self.classes.len()).map_while(move |_| {
+            let name = next?;
+            let info = self.classes.get(name)?;
+            next = info.parent.as_deref();
+            Some((name, info))
+        })
+    }
+
+    fn method(&self, class: &str, name: &str) -> Option<Type> {
+        self.ancestors(class)
+            .find_map(|(_, info)| info.methods.get(name).cloned())
+    }
+
+    fn trait_methods(&self, t: &Expr) -> Option<&HashMap<CompactString, Type>> {
+        match t {
+            Expr::Variable(token) => self.traits.get(&token.lexeme),
+            _ => None,
+        }
+    }
+
+    fn class_method(&self, class: &str, name: &str) -> Option<Type> {
+        self.ancestors(class)
+            .find_map(|(_, info)| info.class_methods.get(name).cloned())
+    }
+
+    fn fits(&self, got: &Type, want: &Type) -> bool {
+        match (got, want) {
+            (Type::Any, _) | (_, Type::Any) => true,
+            // a class named above its declaration has no ancestors yet
+            (Type::Instance(sub), Type::Instance(base)) => {
+                sub == base || self.ancestors(sub).any(|(name, _)| name == base)
+            }
+            (Type::Fun(got_params, got_ret), Type::Fun(want_params, want_ret)) => {
+                got_params.len() == want_params.len()
+                    && got_params
+                        .iter()
+                        .zip(want_params)
+                        .all(|(got, want)| self.fits(want, got))
+                    && self.fits(got_ret, want_ret)
+            }
+            _ => got == want,
+        }
+    }
+
+    fn expect(&mut self, got: &Type, want: &Type, what: impl FnOnce() -> String) {
+        if !self.fits(got, want) {
+            self.errors.push(TypeError::Mismatch {
+                line: self.line,
+                what: what(),
+                want: want.clone(),
+                got: got.clone(),
+            });
+        }
+    }
+
+    fn signature(&mut self, fun_decl: &FunDecl) -> (Vec<Type>, Type) {
+        let params = fun_decl
+            .parameter_types
+            .iter()
+            .map(|annotation| self.annotation(annotation))
+            .collect();
+        (params, self.annotation(&fun_decl.return_type))
+    }
+
+    // Initializers hand back `this` however they end.
+    fn function(&mut self, fun_decl: &FunDecl, params: Vec<Type>, ret: Type, is_init: bool) {
+        self.scopes.push(HashMap::new());
+        for ((param, ty), annotation) in fun_decl
+            .parameters
+            .iter()
+            .zip(params)
+            .zip(&fun_decl.parameter_types)
+        {
+            self.declare(&param.lexeme, ty, annotation.is_some());
+        }
+        self.returns
+            .push((fun_decl.name.lexeme.clone(), ret.clone()));
+        self.check_stmts(&fun_decl.body);
+        self.returns.pop();
+        self.scopes.pop();
+        if !is_init && can_finish(&fun_decl.body) {
+            self.line = fun_decl.body.end_line;
+            let name = &fun_decl.name.lexeme;
+            self.expect(&Type::Nil, &ret, || {
+                format!("implicit return value of '{name}'")
+            });
+        }
+    }
+
+    fn check_stmt(&mut self, stmt: &Stmt) {
+        if let Some(line) = stmt.line() {
+            self.line = line;
+        }
+        match stmt {
+            Stmt::Expr(_, expr) | Stmt::Print(_, expr) => {
+                self.expr(expr);
+            }
+            Stmt::VarDecl(_, name, annotation, init) => {
+                let want = self.annotation(annotation);
+                let got = match init {
+                    Some(init) => self.expr(init),
+                    None => inferred(Type::Nil),
+                };
+                self.expect(&got.ty, &want, || format!("variable '{}'", name.lexeme));
+                match annotation {
+                    Some(_) => self.declare(&name.lexeme, want, true),
+                    // e.g. an annotated lambda keeps its signature
+                    None if got.declared => self.declare(&name.lexeme, got.ty, false),
+                    None => self.declare(&name.lexeme, Type::Any, false),
+                }
+            }
+            Stmt::FunDecl(fun_decl) => {
+                let (params, ret) = self.signature(fun_decl);
+                let ty = Type::Fun(params.clone(), ret.clone().into());
+                // declared first, so it may call itself
+                self.declare(&fun_decl.name.lexeme, ty, false);
+                self.function(fun_decl, params, ret, false);
+            }
+            Stmt::Block(stmts) => {
+                self.scopes.push(HashMap::new());
+                self.check_stmts(stmts);
+                self.scopes.pop();
+            }
+            Stmt::IfThenElse {
+                if_expr,
+                then_stmt,
+                else_stmt,
+                ..
+            } => {
+                self.expr(if_expr);
+                self.check_stmt(then_stmt);
+                if let Some(else_stmt) = else_stmt {
+                    self.check_stmt(else_stmt);
+                }
+            }
+            Stmt::While(_, cond, body) => {
+                self.expr(cond);
+                self.check_stmt(body);
+            }
+            Stmt::Return(_, expr) => {
+                let got = self.expr(expr).ty;
+                if let Some((name, want)) = self.returns.last().cloned() {
+                    self.expect(&got, &want, || format!("return value of '{name}'"));
+                }
+            }
+            Stmt::ClassDecl {
+                name,
+                parent,
+                traits,
+                methods,
+                ..
+            } => {
+                if let Some(parent) = parent {
+                    self.expr(parent);
+                }
+                let parent = match parent {
+                    Some(Expr::Variable(token)) => Some(token.lexeme.clone()),
+                    _ => None,
+                };
+                // the class's own methods, added below, override these
+                let mut from_traits = HashMap::new();
+                for t in traits {
+                    self.expr(t);
+                    if let Some(trait_methods) = self.trait_methods(t) {
+                        from_traits.extend(trait_methods.clone());
+                    }
+                }
+                // known before the methods, which may take or return instances
+                self.classes.insert(
+                    name.lexeme.clone(),
+                    ClassInfo {
+                        parent,
+                        methods: from_traits,
+                        ..Default::default()
+                    },
+                );
+                self.declare(&name.lexeme, Type::Class(name.lexeme.clone()), false);
+                let signatures: Vec<_> = methods.iter().map(|m| self.signature(m)).collect();
+                for (method, (params, ret)) in methods.iter().zip(&signatures) {
+                    let ty = match method.is_getter {
+                        true => ret.clone(),
+                        false => Type::Fun(params.clone(), ret.clone().into()),
+                    };
+                    if let Some(info) = self.classes.get_mut(&name.lexeme) {
+                        let table = match method.is_static {
+                            true => &mut info.class_methods,
+                            false => &mut info.methods,
+                        };
+                        table.insert(method.name.lexeme.clone(), ty);
+                    }
+                }
+                let enclosing = self.this.take();
+                for (method, (params, ret)) in methods.iter().zip(signatures) {
+                    let class = name.lexeme.clone();
+                    self.this = Some(match method.is_static {
+                        true => Type::Class(class),
+                        false => Type::Instance(class),
+                    });
+                    let is_init = method.name.lexeme == "init" && !method.is_static;
+                    self.function(method, params, ret, is_init);
+                }
+                self.this = enclosing;
+            }
+            Stmt::TraitDecl { name, methods, .. } => {
+                self.declare(&name.lexeme, Type::Any, false);
+                let signatures: Vec<_> = methods.iter().map(|m| self.signature(m)).collect();
+                let table = methods
+                    .iter()
+                    .zip(&signatures)
+                    .map(|(method, (params, ret))| {
+                        let ty = match method.is_getter {
+                            true => ret.clone(),
+                            false => Type::Fun(params.clone(), ret.clone().into()),
+                        };
+                        (method.name.lexeme.clone(), ty)
+                    })
+                    .collect();
+                self.traits.insert(name.lexeme.clone(), table);
+                // any class may use it, so `this` could be anything
+                let enclosing = self.this.replace(Type::Any);
+                for (method, (params, ret)) in methods.iter().zip(signatures) {
+                    self.function(method, params, ret, false);
+                }
+                self.this = enclosing;
+            }
+            Stmt::Error(_) => {}
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Typed {
+        match expr {
+            Expr::Literal(value, _) => inferred(match value {
+                Value::VNil => Type::Nil,
+                Value::Bool(_) => Type::Bool,
+                Value::VNumber(_) => Type::Num,
+                Value::VString(_) => Type::Str,
+                _ => Type::Any,
+            }),
+            Expr::Variable(token) => match self.lookup(&token.lexeme) {
+                Some(binding) => Typed {
+                    declared: binding.annotated
+                        || matches!(&binding.ty, Type::Fun(params, ret) if annotated(params, ret)),
+                    ty: binding.ty.clone(),
+                },
+                None => inferred(Type::Any),
+            },
+            Expr::This(_) => inferred(self.this.clone().unwrap_or(Type::Any)),
+            Expr::Super(..) => inferred(Type::Any),
+            Expr::Assign { name, value } => {
+                let got = self.expr(value);
+                match self.lookup(&name.lexeme).cloned() {
+                    Some(Binding {
+                        ty,
+                        annotated: true,
+                    }) => self.expect(&got.ty, &ty, || format!("variable '{}'", name.lexeme)),
+                    // without an annotation, it could now be anything
+                    Some(_) => {
+                        for scope in self.scopes.iter_mut().rev() {
+                            if let Some(binding) = scope.get_mut(&name.lexeme) {
+                                binding.ty = Type::Any;
+                                break;
+                            }
+                        }
+                    }
+                    None => {}
+                }
+                got
+            }
+            Expr::Grouping(expr) => self.expr(expr),
+            Expr::Unary { operator, right } => {
+                let right = self.expr(right);
+                match operator.token {
+                    // may be overloaded with `__neg__`
+                    TokenType::Minus if matches!(right.ty, Type::Instance(_)) => {
+                        inferred(Type::Any)
+                    }
+                    TokenType::Minus => {
+                        if right.declared && !self.fits(&right.ty, &Type::Num) {
+                            self.operands(operator, "number", vec![right.ty]);
+                        }
+                        Typed {
+                            ty: Type::Num,
+                            declared: right.declared,
+                        }
+                    }
+                    _ => inferred(Type::Bool),
+                }
+            }
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                let left = self.expr(left);
+                let right = self.expr(right);
+                self.binary(operator, left, right)
+            }
+            Expr::Logical { left, right, .. } => {
+                let left = self.expr(left);
+                let right = self.expr(right);
+                if left.ty == right.ty {
+                    Typed {
+                        ty: left.ty,
+                        declared: left.declared && right.declared,
+                    }
+                } else {
+                    inferred(Type::Any)
+                }
+            }
+            Expr::Call { callee, arguments } => {
+                let name = match &**callee {
+                    Expr::Variable(token) | Expr::Get { name: token, .. } => token.lexeme.clone(),
+                    _ => "function".into(),
+                };
+                let callee = self.expr(callee).ty;
+                let args: Vec<_> = arguments.iter().map(|arg| self.expr(arg).ty).collect();
+                match callee {
+                    Type::Fun(params, ret) => {
+                        let declared = annotated(&params, &ret);
+                        if declared {
+                            self.arguments(&name, &params, &args);
+                        }
+                        Typed { ty: *ret, declared }
+                    }
+                    Type::Class(class) => {
+                        match self.method(&class, "init") {
+                            Some(Type::Fun(params, ret)) if annotated(&params, &ret) => {
+                                self.arguments(&name, &params, &args)
+                            }
+                            _ => {}
+                        }
+                        inferred(Type::Instance(class))
+                    }
+                    _ => inferred(Type::Any),
+                }
+            }
+            Expr::Get { object, name } => {
+                let object = self.expr(object);
+                self.property(object, name)
+            }
+            Expr::Set {
+                object,
+                name,
+                value,
+            } => {
+                let value = self.expr(value);
+                let object = self.expr(object);
+                self.property(object, name);
+                value
+            }
+            Expr::Lambda { fun_decl, .. } => {
+                let (params, ret) = self.signature(fun_decl);
+                let declared = annotated(&params, &ret);
+                let ty = Type::Fun(params.clone(), ret.clone().into());
+                self.function(fun_decl, params, ret, false);
+                Typed { ty, declared }
+            }
+            Expr::Interpolation(parts) => {
+                for part in parts {
+                    self.expr(part);
+                }
+                inferred(Type::Str)
+            }
+        }
+    }
+
+    fn operands(&mut self, operator: &Token, expected: &'static str, got: Vec<Type>) {
+        self.errors.push(TypeError::Operands {
+            line: self.line,
+            operator: operator.lexeme.clone(),
+            expected,
+            got,
+        });
+    }
+
+    fn binary(&mut self, operator: &Token, left: Typed, right: Typed) -> Typed {
+        use TokenType::*;
+        // an instance on the left may overload the operator
+        if matches!(
+            (&left.ty, &right.ty),
+            (Type::Instance(_), _) | (Type::Any, Type::Instance(_))
+        ) {
+            return inferred(Type::Any);
+        }
+        let declared = left.declared || right.declared;
+        let (left, right) = (left.ty, right.ty);
+        let ty = match operator.token {
+            Minus | Star | Slash => {
+                if declared && (!self.fits(&left, &Type::Num) || !self.fits(&right, &Type::Num)) {
+                    self.operands(operator, "numbers", vec![left, right]);
+                }
+                Type::Num
+            }
+            Plus | Less | LessEqual | Greater | GreaterEqual => {
+                let addable = |ty: &Type| matches!(ty, Type::Any | Type::Num | Type::Str);
+                let ok = match (&left, &right) {
+                    (Type::Any, other) | (other, Type::Any) => addable(other),
+                    (left, right) => left == right && addable(left),
+                };
+                if !ok {
+                    if declared {
+                        self.operands(operator, "two numbers or two strings", vec![left, right]);
+                    }
+                    return inferred(Type::Any);
+                }
+                match operator.token {
+                    Plus if left == right => left,
+                    Plus => Type::Any,
+                    _ => Type::Bool,
+                }
+            }
+            _ => return inferred(Type::Bool),
+        };
+        Typed { ty, declared }
+    }
+
+    fn arguments(&mut self, name: &str, params: &[Type], args: &[Type]) {
+        if params.len() != args.len() {
+            self.errors.push(TypeError::Arity {
+                line: self.line,
+                name: name.into(),
+                want: params.len(),
+                got: args.len(),
+            });
+            return;
+        }
+        for (i, (arg, param)) in args.iter().zip(params).enumerate() {
+            self.expect(arg, param, || format!("argument {} of '{name}'", i + 1));
+        }
+    }
+
+    fn property(&mut self, object: Typed, name: &Token) -> Typed {
+        match object.ty {
+            Type::Instance(class) => {
+                inferred(self.method(&class, &name.lexeme).unwrap_or(Type::Any))
+            }
+            Type::Class(class) => {
+                inferred(self.class_method(&class, &name.lexeme).unwrap_or(Type::Any))
+            }
+            Type::Nil | Type::Bool | Type::Num | Type::Str | Type::Fun(..) if object.declared => {
+                self.errors.push(TypeError::NotInstance {
+                    line: self.line,
+                    name: name.lexeme.clone(),
+                    got: object.ty,
+                });
+                inferred(Type::Any)
+            }
+            _ => inferred(Type::Any),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    fn check(input: &str) -> Result<(), Vec<TypeError>> {
+        let mut scanner = Scanner::new(input);
+        let tokens = scanner.scan_tokens().expect("should scan");
+        let mut parser = Parser::new(&tokens);
+        let stmts = parser.parse().expect("should parse");
+        Checker::default().check(&stmts)
+    }
+
+    #[rstest::rstest]
+    #[case("var x = 1; x = \"s\"; print x - 1;")]
+    #[case("var x: num = 1; var y: any = x; y = \"s\";")]
+    #[case("fun add(a: num, b: num): num { return a + b; }\nprint add(1, 2) * 3;")]
+    #[case("fun f(a) { return a; }\nvar s: str = f(1);")]
+    #[case("fun f(): nil { return; }")]
+    #[case("var s: str = \"a\" + \"b\"; var b: bool = s < \"c\";")]
+    #[case("class A {}\nclass B < A {}\nvar a: A = B();")]
+    // classes can be named above their declaration
+    #[case("fun make(): P { return P(); }\nclass P {}\nvar p: P = make();")]
+    #[case("class A {\n  next(): B { return B(); }\n}\nclass B {}")]
+    #[case(
+        "class P {\n  init(x: num) { this.x = x; }\n  get(): num { return this.x; }\n}\nvar n: num = P(1).get();"
+    )]
+    #[case("fun f(n: num): num { return f(n - 1); }")]
+    #[case("fun f(n: num): num {\n  if (n > 0) return 1;\n  else { return 2; }\n}")]
+    #[case("fun f(): num {\n  while (true) {}\n}")]
+    #[case("fun f(): nil {}\nfun g(): any {}\nfun h() {}")]
+    #[case("class P {\n  init(): P { this.x = 1; }\n}")]
+    // only annotated code is held to its types
+    #[case("if (false) print -true + nil;")]
+    #[case("fun f(a) {}\nif (false) f();")]
+    #[case("var a: any = 1;\nprint a.field;")]
+    #[case("var cache;\nfun get(): num { return cache; }")]
+    #[case("var f = (n: num): num => n + 1;\nvar n: num = f(1);")]
+    #[case(
+        "class M {\n  class sq(n: num): num { return n * n; }\n  class four: num { return this.sq(2); }\n}\nvar n: num = M.sq(M.four);"
+    )]
+    #[case(
+        "class V {\n  __add__(other: V): V { return this; }\n  __neg__(): V { return this; }\n}\nvar a: V = V();\nvar b: V = -a + a;"
+    )]
+    #[case(
+        "trait Named {\n  name(): str { return this.first; }\n}\nclass P with Named {}\nvar s: str = P().name();"
+    )]
+    fn test_check_ok(#[case] input: &str) {
+        assert_eq!(check(input), Ok(()));
+    }
+
+    #[rstest::rstest]
+    #[case(
+        "var x: num = \"s\";",
+        "[line 1] Error: variable 'x' must be num, got str"
+    )]
+    #[case("var x: num;", "[line 1] Error: variable 'x' must be num, got nil")]
+    #[case(
+        "var x: num = 1;\nx = true;",
+        "[line 2] Error: variable 'x' must be num, got bool"
+    )]
+    #[case("var x: int = 1;", "[line 1] Error at 'int': unknown type")]
+    #[case("{ class L {} }\nvar l: L;", "[line 2] Error at 'L': unknown type")]
+    #[case(
+        "class L {}\n{ var L = 1; var l: L; }",
+        "[line 2] Error at 'L': unknown type"
+    )]
+    #[case(
+        "fun add(a: num, b: num): num { return a + b; }\nadd(1, \"2\");",
+        "[line 2] Error: argument 2 of 'add' must be num, got str"
+    )]
+    #[case(
+        "fun add(a: num, b: num): num { return a + b; }\nadd(1);",
+        "[line 2] Error: 'add' takes 2 arguments, got 1"
+    )]
+    #[case(
+        "fun f(): str {\n  return 1;\n}",
+        "[line 2] Error: return value of 'f' must be str, got num"
+    )]
+    #[case(
+        "fun f(): num {\n}",
+        "[line 2] Error: implicit return value of 'f' must be num, got nil"
+    )]
+    #[case(
+        "fun f(n: num): num {\n  if (n > 0) return 1;\n}",
+        "[line 3] Error: implicit return value of 'f' must be num, got nil"
+    )]
+    #[case(
+        "class C {\n  size: num {\n    while (false) return 1;\n  }\n}",
+        "[line 4] Error: implicit return value of 'size' must be num, got nil"
+    )]
+    #[case(
+        "fun f(a: num) {\n  print -a + \"s\";\n}",
+        "[line 2] Error: operands of '+' must be two numbers or two strings, got num and str"
+    )]
+    #[case(
+        "var b: bool = true;\nprint -b;",
+        "[line 2] Error: operand of '-' must be a number, got bool"
+    )]
+    #[case(
+        "var n: num = 1;\nprint n < \"1\";",
+        "[line 2] Error: operands of '<' must be two numbers or two strings, got num and str"
+    )]
+    #[case(
+        "var s: str = \"a\";\nprint s.length;",
+        "[line 2] Error: only instances have properties, got str for 'length'"
+    )]
+    #[case(
+        "class A {}\nclass B {}\nvar a: A = B();",
+        "[line 3] Error: variable 'a' must be A, got B"
+    )]
+    #[case(
+        "class P {\n  init(x: num) {}\n}\nP(\"x\");",
+        "[line 4] Error: argument 1 of 'P' must be num, got str"
+    )]
+    #[case(
+        "class M {\n  class sq(n: num): num { return n * n; }\n}\nM.sq(\"2\");",
+        "[line 4] Error: argument 1 of 'sq' must be num, got str"
+    )]
+    #[case(
+        "class C {\n  size: num { return 1; }\n}\nvar s: str = C().size;",
+        "[line 4] Error: variable 's' must be str, got num"
+    )]
+    #[case(
+        "var f = (n: num) => n;\nf(\"s\");",
+        "[line 2] Error: argument 1 of 'f' must be num, got str"
+    )]
+    #[case(
+        "fun f(): num { return 1; }\nvar s: str = f();",
+        "[line 2] Error: variable 's' must be str, got num"
+    )]
+    #[case(
+        "trait Named {\n  name(): str { return \"p\"; }\n}\nclass P with Named {}\nvar n: num = P().name();",
+        "[line 5] Error: variable 'n' must be num, got str"
+    )]
+    fn test_check_error(#[case] input: &str, #[case] want: &str) {
+        let errors = check(input).expect_err("should not type check");
+        let got: Vec<_> = errors.iter().map(TypeError::to_string).collect();
+        assert_eq!(got, vec![want]);
+    }
+
+    #[test]
+    fn test_check_keeps_globals() {
+        let mut checker = Checker::default();
+        let mut check_line = |input: &str| {
+            let mut scanner = Scanner::new(input);
+            let tokens = scanner.scan_tokens().expect("should scan");
+            let stmts = Parser::new(&tokens).parse().expect("should parse");
+            checker.check(&stmts)
+        };
+        assert_eq!(check_line("class P {}\nvar n: num = 1;"), Ok(()));
+        assert_eq!(check_line("var p: P = P();"), Ok(()));
+        let errors = check_line("var s: str = n;").expect_err("should not type check");
+        assert_eq!(
+            errors[0].to_string(),
+            "[line 1] Error: variable 's' must be str, got num"
+        );
+    }
+
+    #[test]
+    fn test_check_redefined_class_cycle() {
+        // each class ends up naming the other as its superclass
+        let input = "class A {}\nclass B < A {}\nclass A < B {}\nvar a: A = B();";
+        assert_eq!(check(input), Ok(()));
+    }
+}
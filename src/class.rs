@@ -13,8 +13,26 @@ use std::rc::Rc;
 #[derive(Debug, Clone)]
 pub struct LoxClass {
     pub name: CompactString,
-    pub methods: HashMap<CompactString, LoxFunction>,
+    // behind a RefCell, so define_method can add to it at runtime
+    pub methods: RefCell<HashMap<CompactString, LoxFunction>>,
+    // static methods, called on the class itself
+    pub class_methods: HashMap<CompactString, LoxFunction>,
     pub parent: Option<Rc<LoxClass>>,
+    pub traits: Vec<Rc<LoxTrait>>,
+}
+
+// A named set of methods that classes take on with `with`. Its methods are
+// looked up after the class's own, but before its superclass's.
+#[derive(Debug)]
+pub struct LoxTrait {
+    pub name: CompactString,
+    pub methods: HashMap<CompactString, LoxFunction>,
+}
+
+impl fmt::Display for LoxTrait {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
 }
 
 impl fmt::Display for LoxClass {
@@ -23,7 +41,12 @@ impl fmt::Display for LoxClass {
     }
 }
 
-impl LoxCallable for LoxClass {
+// Implemented on the Rc, so each instance can share its class.
+impl LoxCallable for Rc<LoxClass> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
     fn arity(&self) -> usize {
         match self.find_method("init") {
             None => 0,
@@ -32,12 +55,14 @@ impl LoxCallable for LoxClass {
     }
 
     fn call(&self, interpreter: &mut Interpreter, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        interpreter.track_object()?;
         let instance = Rc::new(LoxInstance {
-            class: self.clone(),
+            class: Rc::clone(self),
             fields: Default::default(),
         });
         if let Some(init) = self.find_method("init") {
-            init.bind(instance.clone()).call(interpreter, args)?;
+            init.bind(Value::Object(instance.clone()))
+                .call(interpreter, args)?;
         }
         Ok(Value::Object(instance))
     }
@@ -45,21 +70,97 @@ impl LoxCallable for LoxClass {
 
 impl LoxClass {
     pub fn find_method(&self, name: &str) -> Option<LoxFunction> {
+        // a class whose traits share a method is an error, from the resolver
+        // or when the class is declared, so at most one trait can have it
         self.methods
+            .borrow()
             .get(name)
+            .or_else(|| self.traits.iter().find_map(|t| t.methods.get(name)))
             .cloned()
             .or_else(|| self.parent.as_ref().and_then(|p| p.find_method(name)))
     }
+
+    // Adds or replaces a method; instances already made pick it up too.
+    pub fn define_method(&self, name: &str, method: LoxFunction) {
+        self.methods.borrow_mut().insert(name.into(), method);
+    }
+
+    // Whether this is `class` or one of its subclasses.
+    pub fn inherits(&self, class: &LoxClass) -> bool {
+        std::ptr::eq(self, class) || self.parent.as_ref().is_some_and(|p| p.inherits(class))
+    }
+
+    // Whether this or one of its superclasses is declared `with` the trait.
+    pub fn uses(&self, trait_: &LoxTrait) -> bool {
+        self.traits.iter().any(|t| std::ptr::eq(&**t, trait_))
+            || self.parent.as_ref().is_some_and(|p| p.uses(trait_))
+    }
+
+    // The names of all methods an instance can call, sorted.
+    pub fn method_names(&self) -> Vec<CompactString> {
+        let mut names: Vec<_> = self.methods.borrow().keys().cloned().collect();
+        for t in &self.traits {
+            names.extend(t.methods.keys().cloned());
+        }
+        if let Some(parent) = &self.parent {
+            names.extend(parent.method_names());
+        }
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    pub fn find_class_method(&self, name: &str) -> Option<LoxFunction> {
+        self.class_methods
+            .get(name)
+            .cloned()
+            .or_else(|| self.parent.as_ref().and_then(|p| p.find_class_method(name)))
+    }
+}
+
+// A method read as a property is bound to `this`, or run straight away if
+// it's a getter.
+pub fn bind_property(
+    interpreter: &mut Interpreter,
+    method: LoxFunction,
+    this: Value,
+) -> Result<Value, RuntimeError> {
+    let method = method.bind(this);
+    if method.definition.is_getter {
+        method.call(interpreter, vec![])
+    } else {
+        Ok(Value::Callable(Rc::new(method)))
+    }
 }
 
 #[derive(Debug)]
 pub struct LoxInstance {
-    // Clone for now, but this should maybe be refcounted
-    class: LoxClass,
+    class: Rc<LoxClass>,
 
     fields: RefCell<HashMap<CompactString, Value>>,
 }
 
+impl LoxInstance {
+    pub fn class(&self) -> &Rc<LoxClass> {
+        &self.class
+    }
+
+    pub fn field(&self, name: &str) -> Option<Value> {
+        self.fields.borrow().get(name).cloned()
+    }
+
+    pub fn set_field(&self, name: &str, value: Value) {
+        self.fields.borrow_mut().insert(name.into(), value);
+    }
+
+    // sorted, so they come out the same every time
+    pub fn field_names(&self) -> Vec<CompactString> {
+        let mut names: Vec<_> = self.fields.borrow().keys().cloned().collect();
+        names.sort();
+        names
+    }
+}
+
 impl fmt::Display for LoxInstance {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{} instance", self.class)
@@ -67,28 +168,25 @@ impl fmt::Display for LoxInstance {
 }
 
 pub trait GetSet {
-    fn get(&self, name: &Token) -> Result<Value, RuntimeError>;
+    fn get(&self, interpreter: &mut Interpreter, name: &Token) -> Result<Value, RuntimeError>;
     fn set(&self, name: &Token, value: Value);
 }
 
 impl GetSet for Rc<LoxInstance> {
-    fn get(&self, name: &Token) -> Result<Value, RuntimeError> {
-        let borrow = self.fields.borrow();
-        if let Some(value) = borrow.get(&name.lexeme) {
-            return Ok(value.clone());
+    fn get(&self, interpreter: &mut Interpreter, name: &Token) -> Result<Value, RuntimeError> {
+        if let Some(value) = self.field(&name.lexeme) {
+            return Ok(value);
         }
         if let Some(method) = self.class.find_method(&name.lexeme) {
-            let x: Rc<LoxInstance> = self.clone();
-            return Ok(Value::Callable(Rc::new(method.bind(x))));
+            return bind_property(interpreter, method, Value::Object(self.clone()));
         }
         Err(RuntimeError::UndefinedProperty {
-            line: "TODO".into(),
+            line: name.line.to_string().into(),
             name: name.lexeme.to_owned(),
         })
     }
 
     fn set(&self, name: &Token, value: Value) {
-        let mut borrow = self.fields.borrow_mut();
-        borrow.insert(name.lexeme.to_owned(), value);
+        self.set_field(&name.lexeme, value);
     }
 }
@@ -0,0 +1,420 @@
+use crate::error::RuntimeError;
+use crate::interpreter::Hook;
+use crate::interpreter::Interpreter;
+use crate::models::Expr;
+use crate::models::Stmt;
+use crate::models::Value;
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use std::collections::BTreeSet;
+use std::io::BufRead;
+use std::io::Write;
+use std::rc::Rc;
+
+const HELP: &str = "\
+break <line>    pause whenever <line> runs (b)
+delete <line>   remove a breakpoint (d)
+step            run one statement, entering calls (s)
+next            run one statement, stepping over calls (n)
+out             run until the current function returns (o)
+continue        run until the next breakpoint (c)
+print <expr>    evaluate an expression in the paused frame (p)
+env             show the scopes visible from here
+backtrace       show the call stack (bt)
+quit            stop the script (q)";
+
+// Where to pause next, besides at breakpoints. Depths count call frames.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Continue,
+    Step,
+    Next(usize),
+    Out(usize),
+}
+
+// An interactive, line-oriented debugger. It pauses before the first
+// statement so breakpoints can be set, reading commands from input and
+// writing to output.
+pub struct Debugger<R, W> {
+    source: Vec<String>,
+    input: R,
+    output: W,
+    breakpoints: BTreeSet<usize>,
+    mode: Mode,
+    // the last statement run, with its line and call depth
+    previous: Option<(*const Stmt, usize, usize)>,
+}
+
+impl<R: BufRead, W: Write> Debugger<R, W> {
+    pub fn new(source: &str, input: R, output: W) -> Self {
+        Self {
+            source: source.lines().map(str::to_owned).collect(),
+            input,
+            output,
+            breakpoints: BTreeSet::new(),
+            mode: Mode::Step,
+            previous: None,
+        }
+    }
+
+    fn should_pause(&self, stmt: *const Stmt, line: usize, depth: usize) -> bool {
+        // a line holding several statements (`if (x) print x;`) pauses once,
+        // but a loop coming back around to the same statement pauses again
+        if let Some((previous, previous_line, previous_depth)) = self.previous
+            && previous != stmt
+            && (previous_line, previous_depth) == (line, depth)
+        {
+            return false;
+        }
+        self.breakpoints.contains(&line)
+            || match self.mode {
+                Mode::Continue => false,
+                Mode::Step => true,
+                Mode::Next(paused) => depth <= paused,
+                Mode::Out(paused) => depth < paused,
+            }
+    }
+
+    fn show_line(&mut self, line: usize) {
+        let text = self.source.get(line - 1).map_or("", |text| text.trim());
+        writeln!(self.output, "line {line}: {text}").expect("writes should not fail");
+    }
+
+    fn show_environment(&mut self, interpreter: &Interpreter) {
+        let mut env = Some(&interpreter.environment);
+        let mut depth = 0;
+        while let Some(scope) = env {
+            if Rc::ptr_eq(scope, &interpreter.globals) {
+                writeln!(self.output, "globals:")
+            } else {
+                writeln!(self.output, "scope {depth}:")
+            }
+            .expect("writes should not fail");
+            for (name, value) in scope.bindings() {
+                writeln!(self.output, "  {name} = {}", show(&value))
+                    .expect("writes should not fail");
+            }
+            env = scope.parent();
+            depth += 1;
+        }
+    }
+
+    fn show_backtrace(&mut self, interpreter: &Interpreter) {
+        for (i, frame) in interpreter.frames.iter().rev().enumerate() {
+            writeln!(
+                self.output,
+                "#{i} {} at line {}",
+                frame.function, frame.line
+            )
+            .expect("writes should not fail");
+        }
+    }
+}
+
+impl<R: BufRead, W: Write> Hook for Debugger<R, W> {
+    fn statement(
+        &mut self,
+        interpreter: &mut Interpreter,
+        line: usize,
+        stmt: &Stmt,
+    ) -> Result<(), RuntimeError> {
+        let depth = interpreter.frames.len();
+        let pause = self.should_pause(stmt, line, depth);
+        self.previous = Some((stmt, line, depth));
+        if !pause {
+            return Ok(());
+        }
+        self.show_line(line);
+        loop {
+            write!(self.output, "(debug) ").expect("writes should not fail");
+            self.output.flush().expect("writes should not fail");
+            let mut command = String::new();
+            if !matches!(self.input.read_line(&mut command), Ok(n) if n > 0) {
+                // out of commands -- let the script run to the end
+                self.breakpoints.clear();
+                self.mode = Mode::Continue;
+                return Ok(());
+            }
+            let (command, rest) = command
+                .trim()
+                .split_once(' ')
+                .unwrap_or((command.trim(), ""));
+            let rest = rest.trim();
+            match command {
+                "" => {}
+                "s" | "step" => {
+                    self.mode = Mode::Step;
+                    return Ok(());
+                }
+                "n" | "next" => {
+                    self.mode = Mode::Next(depth);
+                    return Ok(());
+                }
+                "o" | "out" => {
+                    self.mode = Mode::Out(depth);
+                    return Ok(());
+                }
+                "c" | "continue" => {
+                    self.mode = Mode::Continue;
+                    return Ok(());
+                }
+                "b" | "break" => match rest.parse::<usize>() {
+                    Ok(n) if n > 0 => {
+                        self.breakpoints.insert(n);
+                        writeln!(self.output, "breakpoint at line {n}")
+                    }
+                    _ => writeln!(self.output, "usage: break <line>"),
+                }
+                .expect("writes should not fail"),
+                "d" | "delete" => match rest.parse::<usize>() {
+                    Ok(n) if self.breakpoints.remove(&n) => {
+                        writeln!(self.output, "deleted breakpoint at line {n}")
+                    }
+                    Ok(n) => writeln!(self.output, "no breakpoint at line {n}"),
+                    Err(_) => writeln!(self.output, "usage: delete <line>"),
+                }
+                .expect("writes should not fail"),
+                "p" | "print" => {
+                    let text = match evaluate(interpreter, line, rest) {
+                        Ok(value) => show(&value),
+                        Err(msg) => msg,
+                    };
+                    writeln!(self.output, "{text}").expect("writes should not fail");
+                }
+                "env" => self.show_environment(interpreter),
+                "bt" | "backtrace" => self.show_backtrace(interpreter),
+                "q" | "quit" => {
+                    return Err(RuntimeError::Stopped {
+                        line: format!("{line}").into(),
+                    });
+                }
+                "h" | "help" => writeln!(self.output, "{HELP}").expect("writes should not fail"),
+                _ => writeln!(self.output, "unknown command '{command}', try 'help'")
+                    .expect("writes should not fail"),
+            }
+        }
+    }
+}
+
+fn show(value: &Value) -> String {
+    match value {
+        Value::VString(s) => format!("\"{s}\""),
+        _ => format!("{value}"),
+    }
+}
+
+// Evaluates an expression typed at the prompt as if it appeared in the paused
+// frame.
+fn evaluate(interpreter: &mut Interpreter, line: usize, src: &str) -> Result<Value, String> {
+    let src = format!("{src};");
+    let mut scanner = Scanner::new(&src);
+    let tokens = scanner.scan_tokens().map_err(|err| err.to_string())?;
+    let mut parser = Parser::new(&tokens);
+    let stmts = parser.parse().map_err(|err| err.to_string())?;
+    let expr = match &stmts.stmts[..] {
+        [Stmt::Expr(_, expr)] => expr,
+        _ => return Err("expected an expression".into()),
+    };
+    let mut bound = vec![];
+    let result = bind_locals(interpreter, expr, &mut bound).and_then(|_| {
+        interpreter
+            .eval_expr(line, expr)
+            .map_err(|err| err.to_string())
+    });
+    for expr in bound {
+        interpreter.resolutions.remove(&expr);
+    }
+    result
+}
+
+// The resolver never saw the expression, so point each name at wherever it
+// is defined from the paused environment.
+fn bind_locals(
+    interpreter: &mut Interpreter,
+    expr: &Expr,
+    bound: &mut Vec<*const Expr>,
+) -> Result<(), String> {
+    let mut bind = |name: &str| match interpreter.environment.depth_of(name) {
+        Some(depth) => {
+            interpreter.resolutions.insert(expr, depth);
+            bound.push(expr);
+            true
+        }
+        None => false,
+    };
+    match expr {
+        Expr::Literal(..) => Ok(()),
+        Expr::Variable(token) => {
+            bind(&token.lexeme);
+            Ok(())
+        }
+        Expr::This(_) => match bind("this") {
+            true => Ok(()),
+            false => Err("'this' is not defined here".into()),
+        },
+        Expr::Super(..) => Err("can't evaluate 'super' in the debugger".into()),
+        Expr::Lambda { .. } => Err("can't define functions in the debugger".into()),
+        Expr::Assign { name, value } => {
+            bind(&name.lexeme);
+            bind_locals(interpreter, value, bound)
+        }
+        Expr::Grouping(inner) | Expr::Unary { right: inner, .. } => {
+            bind_locals(interpreter, inner, bound)
+        }
+        Expr::Get { object, .. } => bind_locals(interpreter, object, bound),
+        Expr::Binary { left, right, .. }
+        | Expr::Logical { left, right, .. }
+        | Expr::Set {
+            object: left,
+            value: right,
+            ..
+        } => {
+            bind_locals(interpreter, left, bound)?;
+            bind_locals(interpreter, right, bound)
+        }
+        Expr::Call { callee, arguments } => {
+            bind_locals(interpreter, callee, bound)?;
+            arguments
+                .iter()
+                .try_for_each(|arg| bind_locals(interpreter, arg, bound))
+        }
+        Expr::Interpolation(parts) => parts
+            .iter()
+            .try_for_each(|part| bind_locals(interpreter, part, bound)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::LoxError;
+    use crate::output::SharedBuffer;
+    use crate::resolver::Resolver;
+
+    // Runs a script under the debugger, returning the result, the debugger's
+    // transcript and the script's own output.
+    fn debug(src: &str, commands: &'static str) -> (Result<(), LoxError>, String, String) {
+        let transcript = SharedBuffer::default();
+        let output = SharedBuffer::default();
+        let mut interpreter = Interpreter {
+            stdout: Box::new(output.clone()),
+            hook: Some(Box::new(Debugger::new(
+                src,
+                commands.as_bytes(),
+                transcript.clone(),
+            ))),
+            ..Default::default()
+        };
+        let result = (|| {
+            let mut scanner = Scanner::new(src);
+            let tokens = scanner.scan_tokens()?;
+            let mut parser = Parser::new(&tokens);
+            let stmts = parser.parse()?;
+            let mut resolver = Resolver::default();
+            interpreter.resolutions = resolver.resolve(&stmts)?;
+            Ok(interpreter.interpret(&stmts)?)
+        })();
+        (result, transcript.contents(), output.contents())
+    }
+
+    #[test]
+    fn test_breakpoint_and_inspect() {
+        let src = r#"fun add(a, b) {
+  var sum = a + b;
+  return sum;
+}
+var x = 1;
+print add(x, 2);
+print x;
+"#;
+        let commands = "b 2\nc\nbt\nenv\np a + b\nn\np sum * 10\np nope\nout\np x = \"two\"\nc\n";
+        let (result, transcript, output) = debug(src, commands);
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            transcript,
+            r#"line 1: fun add(a, b) {
+(debug) breakpoint at line 2
+(debug) line 2: var sum = a + b;
+(debug) #0 add at line 2
+#1 script at line 6
+(debug) scope 0:
+  a = 1
+  b = 2
+globals:
+  add = <fn add>
+  arity = arity
+  clock = clock
+  define_method = define_method
+  fields = fields
+  getEnv = getEnv
+  get_field = get_field
+  has_field = has_field
+  methods = methods
+  name = name
+  readFile = readFile
+  set_field = set_field
+  superclass = superclass
+  type = type
+  writeFile = writeFile
+  x = 1
+(debug) 3
+(debug) line 3: return sum;
+(debug) 30
+(debug) [line 1] Error: undefined variable: 'nope'
+(debug) line 7: print x;
+(debug) "two"
+(debug) "#
+        );
+        assert_eq!(output, "3\ntwo\n");
+    }
+
+    #[test]
+    fn test_step() {
+        let src = "fun f() {\n  print 1;\n}\nf(); print 2;\nprint 3;\n";
+        let (result, transcript, output) = debug(src, "s\ns\ns\ns\nn\n");
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            transcript,
+            "line 1: fun f() {
+(debug) line 4: f(); print 2;
+(debug) line 2: print 1;
+(debug) line 4: f(); print 2;
+(debug) line 5: print 3;
+(debug) "
+        );
+        assert_eq!(output, "1\n2\n3\n");
+    }
+
+    #[test]
+    fn test_loop_breakpoint() {
+        let src = "var i = 0;\nwhile (i < 3) {\n  i = i + 1;\n}\nprint i;\n";
+        let (result, transcript, _) = debug(src, "b 3\nc\np i\nd 3\nd 3\nc\n");
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            transcript,
+            "line 1: var i = 0;
+(debug) breakpoint at line 3
+(debug) line 3: i = i + 1;
+(debug) 0
+(debug) deleted breakpoint at line 3
+(debug) no breakpoint at line 3
+(debug) "
+        );
+
+        let (result, transcript, _) = debug(src, "b 3\nc\nc\nc\np i\nc\n");
+        assert_eq!(result, Ok(()));
+        assert!(transcript.ends_with("line 3: i = i + 1;\n(debug) 2\n(debug) "));
+    }
+
+    #[test]
+    fn test_quit() {
+        let (result, _, output) = debug("print 1;\nprint 2;\n", "n\nq\n");
+        assert_eq!(
+            result,
+            Err(LoxError::RuntimeError(RuntimeError::Stopped {
+                line: "2".into()
+            }))
+        );
+        assert_eq!(output, "1\n");
+    }
+}
@@ -29,6 +29,33 @@ impl Environment {
         })
     }
 
+    pub fn parent(&self) -> Option<&Rc<Self>> {
+        self.parent.as_ref()
+    }
+
+    // Name/value pairs defined directly in this scope, sorted by name.
+    pub fn bindings(&self) -> Vec<(CompactString, Value)> {
+        let mut bindings: Vec<_> = self
+            .table
+            .borrow()
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        bindings.sort_by(|a, b| a.0.cmp(&b.0));
+        bindings
+    }
+
+    // How many scopes up the chain a name is defined, like the resolver's depth.
+    pub fn depth_of(&self, name: &str) -> Option<usize> {
+        if self.table.borrow().contains_key(name) {
+            return Some(0);
+        }
+        self.parent
+            .as_ref()
+            .and_then(|p| p.depth_of(name))
+            .map(|depth| depth + 1)
+    }
+
     fn pop(self: Rc<Self>) -> Option<Rc<Self>> {
         // this is a silly method -- we shouldn't need to clone
         // to move out. But self is Rc and so immutable
@@ -114,7 +141,10 @@ impl Env for Environment {
         if depth == 0 {
             self.assign(name, value)
         } else {
-            self.assign_at(name, value, depth - 1)
+            self.parent
+                .as_ref()
+                .expect("we checked parent depth earlier")
+                .assign_at(name, value, depth - 1)
         }
     }
 }
@@ -205,6 +235,20 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_assign_at() -> Result<(), RuntimeError> {
+        let env = Rc::new(Environment::default());
+        env.define("a", VNumber(1.0));
+        let child = env.push();
+        child.define("a", VNumber(2.0));
+
+        // skips the shadowing definition
+        child.assign_at("a", VNumber(3.0), 1)?;
+        assert_eq!(child.get_at("a", 0), Ok(VNumber(2.0)));
+        assert_eq!(env.get("a"), Ok(VNumber(3.0)));
+        Ok(())
+    }
+
     #[test]
     fn test_closure() -> Result<(), RuntimeError> {
         let env = Rc::new(Environment::default());
@@ -232,6 +276,18 @@ mod tests {
         assert_eq!(top.get_at("name", 0)?, VString("top".into()));
         assert_eq!(top.get_at("name", 1)?, VString("mid".into()));
         assert_eq!(top.get_at("name", 2)?, VString("base".into()));
+
+        base.define("only_base", Bool(true));
+        assert_eq!(top.depth_of("name"), Some(0));
+        assert_eq!(top.depth_of("only_base"), Some(2));
+        assert_eq!(top.depth_of("nowhere"), None);
+        assert_eq!(
+            base.bindings(),
+            vec![
+                ("name".into(), VString("base".into())),
+                ("only_base".into(), Bool(true)),
+            ]
+        );
         Ok(())
     }
 }
@@ -1,3 +1,4 @@
+use crate::checker::TypeError;
 use crate::models::Token;
 use crate::models::TokenType;
 use crate::models::Value;
@@ -6,6 +7,7 @@ use compact_str::CompactString;
 use std::fmt::Display;
 use std::fmt::Write;
 use std::io;
+use std::time::Duration;
 use thiserror::Error;
 
 pub type LoxResult<T> = Result<T, LoxError>;
@@ -17,12 +19,34 @@ pub struct ScanError {
     pub msg: CompactString,
 }
 
+// Partial parses keep a placeholder where a statement failed to parse, which
+// can't be written back out.
+#[derive(Debug, Error, PartialEq)]
+#[error("[line {line}] Error: can't format a statement that failed to parse")]
+pub struct FormatError {
+    pub line: usize,
+}
+
 #[derive(Debug, Error, PartialEq)]
 pub enum ParseError {
-    #[error("[line {0}] {1}")]
-    GeneralError(usize, CompactString),
-    #[error("[line {0}] Error: unexpected eof")]
-    UnexpectedEof(usize),
+    #[error("[line {}] Error at {}: expected {} {context}", found.line, location(found), one_of(expected))]
+    Expected {
+        expected: Vec<TokenType>,
+        found: Token,
+        context: &'static str,
+    },
+
+    #[error("[line {}] Error at {}: expected expression", found.line, location(found))]
+    ExpectedExpression { found: Token },
+
+    #[error("[line {}] Error at {}: invalid assignment target", equals.line, location(equals))]
+    InvalidAssignmentTarget { equals: Token },
+
+    #[error("[line {}] Error at {}: can't have more than 255 {what}", found.line, location(found))]
+    TooMany { found: Token, what: &'static str },
+
+    #[error("[line {0}] Error: too many errors, giving up")]
+    TooManyErrors(usize),
 }
 
 // TODO: I think its probably still correct that there should be two levels to the hierarchy:
@@ -39,11 +63,13 @@ pub enum RuntimeError {
         want: usize,
     },
 
-    #[error("[line {line}] Error: type mismatch: {lhs} vs {rhs}")]
+    #[error("[line {line}] Error: {}", type_mismatch(operator, expected, operands))]
     TypeMismatch {
         line: CompactString,
-        lhs: Box<Value>,
-        rhs: Box<Value>,
+        operator: CompactString,
+        // what the operands must be, e.g. "numbers"
+        expected: &'static str,
+        operands: Vec<Value>,
     },
 
     #[error("[line {line}] Error: division by zero")]
@@ -58,18 +84,94 @@ pub enum RuntimeError {
         name: CompactString,
     },
 
-    #[error("[line {line}]: Error: undefined property: '{name}'")]
+    #[error("[line {line}] Error: undefined property: '{name}'")]
     UndefinedProperty {
         line: CompactString,
         name: CompactString,
     },
 
-    #[error("[line {line} Error: non callable called {value}")]
+    #[error("[line {line}] Error: only instances have properties, got {value} for '{name}'")]
+    PropertyOnNonInstance {
+        line: CompactString,
+        name: CompactString,
+        value: Value,
+    },
+
+    #[error("[line {line}] Error: only instances have fields, got {value} for '{name}'")]
+    FieldOnNonInstance {
+        line: CompactString,
+        name: CompactString,
+        value: Value,
+    },
+
+    #[error("[line {line}] Error: superclass must be a class, got {value}")]
+    SuperclassNotClass { line: CompactString, value: Value },
+
+    #[error("[line {line}] Error: right operand of 'is' must be a class or trait, got {value}")]
+    IsNotClass { line: CompactString, value: Value },
+
+    #[error("[line {line}] Error: can only use traits with 'with', got {value}")]
+    WithNotTrait { line: CompactString, value: Value },
+
+    // what the resolver reports, for traits it couldn't see
+    #[error("[line {line}] Error at '{class}': method '{method}' comes from both '{first}' and '{second}'")]
+    TraitConflict {
+        line: CompactString,
+        class: CompactString,
+        method: CompactString,
+        first: CompactString,
+        second: CompactString,
+    },
+
+    #[error("[line {line}] Error: non callable called {value}")]
     NonCallableCalled { line: CompactString, value: Value },
 
     #[error("[line {line}] return {value}(not an error!)")]
     Return { line: CompactString, value: Value },
 
+    #[error("[line {line}] Error: step limit of {limit} exceeded")]
+    StepLimit { line: CompactString, limit: u64 },
+
+    #[error("[line {line}] Error: stack overflow: more than {limit} nested calls")]
+    StackOverflow { line: CompactString, limit: usize },
+
+    #[error("[line {line}] Error: more than {limit} objects allocated")]
+    ObjectLimit { line: CompactString, limit: usize },
+
+    #[error("[line {line}] Error: more than {limit} bytes of strings allocated")]
+    StringLimit { line: CompactString, limit: usize },
+
+    #[error("[line {line}] Error: timed out after {limit:?}")]
+    Timeout {
+        line: CompactString,
+        limit: Duration,
+    },
+
+    #[error("[line {line}] Error: permission denied: {action}")]
+    PermissionDenied {
+        line: CompactString,
+        action: CompactString,
+    },
+
+    #[error("[line {line}] Error: {native}: {msg}")]
+    NativeError {
+        line: CompactString,
+        native: &'static str,
+        msg: CompactString,
+    },
+
+    #[error("[line {line}] Error: can't write output: {msg}")]
+    OutputError {
+        line: CompactString,
+        msg: CompactString,
+    },
+
+    #[error("[line {line}] Error: stopped by the debugger")]
+    Stopped { line: CompactString },
+
+    #[error("[line {line}] Error: can't run a statement that failed to parse")]
+    Unparsed { line: CompactString },
+
     #[error("[line {line}] break (not an error!)")]
     Break { line: CompactString },
 }
@@ -85,10 +187,86 @@ pub enum LoxError {
     #[error("{}", join_all(.0))]
     ResolverErrors(Vec<ResolverError>),
 
+    #[error("{}", join_all(.0))]
+    TypeErrors(Vec<TypeError>),
+
+    #[error("{0}")]
+    FormatError(#[from] FormatError),
+
     #[error("{0}")]
     RuntimeError(#[from] RuntimeError),
 }
 
+impl LoxError {
+    // The exit codes jlox uses, from sysexits.h: EX_DATAERR for a script that
+    // doesn't compile and EX_SOFTWARE for one that fails while running.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            LoxError::RuntimeError(_) => 70,
+            _ => 65,
+        }
+    }
+}
+
+// Every variant carries a line; this picks it out of whichever one we have.
+macro_rules! line_of {
+    ($err:expr) => {
+        match $err {
+            RuntimeError::ArityMismatch { line, .. }
+            | RuntimeError::TypeMismatch { line, .. }
+            | RuntimeError::ZeroDivError { line }
+            | RuntimeError::SystemTimeError { line }
+            | RuntimeError::UndefinedVariable { line, .. }
+            | RuntimeError::UndefinedProperty { line, .. }
+            | RuntimeError::PropertyOnNonInstance { line, .. }
+            | RuntimeError::FieldOnNonInstance { line, .. }
+            | RuntimeError::SuperclassNotClass { line, .. }
+            | RuntimeError::IsNotClass { line, .. }
+            | RuntimeError::WithNotTrait { line, .. }
+            | RuntimeError::TraitConflict { line, .. }
+            | RuntimeError::NonCallableCalled { line, .. }
+            | RuntimeError::Return { line, .. }
+            | RuntimeError::StepLimit { line, .. }
+            | RuntimeError::StackOverflow { line, .. }
+            | RuntimeError::ObjectLimit { line, .. }
+            | RuntimeError::StringLimit { line, .. }
+            | RuntimeError::Timeout { line, .. }
+            | RuntimeError::PermissionDenied { line, .. }
+            | RuntimeError::NativeError { line, .. }
+            | RuntimeError::OutputError { line, .. }
+            | RuntimeError::Stopped { line }
+            | RuntimeError::Unparsed { line }
+            | RuntimeError::Break { line } => line,
+        }
+    };
+}
+
+impl RuntimeError {
+    pub fn line(&self) -> &str {
+        line_of!(self)
+    }
+
+    // Errors raised away from the source, e.g. by the environment, don't know
+    // their line; whoever evaluated the expression fills it in.
+    pub fn with_line(mut self, line: usize) -> Self {
+        let current = line_of!(&mut self);
+        if current == "TODO" {
+            *current = line.to_string().into();
+        }
+        self
+    }
+}
+
+impl MainError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            // EX_IOERR
+            MainError::IoError(_) => 74,
+            MainError::LoxError(err) => err.exit_code(),
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum MainError {
     #[error("io error: {0}")]
@@ -109,20 +287,51 @@ fn join_all<T: Display>(items: &[T]) -> String {
     out
 }
 
-impl<S: AsRef<str>> From<(Token, S)> for ParseError {
-    fn from((token, msg): (Token, S)) -> ParseError {
-        let r#where = if token.token == TokenType::Eof {
-            "end".to_owned()
-        } else {
-            format!("'{}'", token.lexeme)
-        };
-        ParseError::GeneralError(
-            token.line,
-            format!("Error at {}: {}", r#where, msg.as_ref()).into(),
-        )
+// e.g. "operand of '-' must be a number, got string "x""
+fn type_mismatch(operator: &str, expected: &str, operands: &[Value]) -> String {
+    let (noun, article) = match operands.len() {
+        1 => ("operand", "a "),
+        _ => ("operands", ""),
+    };
+    let got: Vec<_> = operands.iter().map(describe).collect();
+    format!(
+        "{noun} of '{operator}' must be {article}{expected}, got {}",
+        got.join(" and ")
+    )
+}
+
+fn describe(value: &Value) -> String {
+    match value {
+        Value::VNil => "nil".to_owned(),
+        Value::VString(s) => format!("string {s:?}"),
+        Value::Object(_) => value.to_string(),
+        _ => format!("{} {value}", value.type_name()),
     }
 }
 
+fn location(token: &Token) -> String {
+    if token.token == TokenType::Eof {
+        "end".to_owned()
+    } else {
+        format!("'{}'", token.lexeme)
+    }
+}
+
+fn one_of(expected: &[TokenType]) -> String {
+    let mut out = String::new();
+    for (i, token_type) in expected.iter().enumerate() {
+        if i > 0 {
+            out.push_str(if i + 1 == expected.len() {
+                " or "
+            } else {
+                ", "
+            });
+        }
+        write!(&mut out, "{token_type}").expect("must be able to append to string");
+    }
+    out
+}
+
 impl From<Vec<ScanError>> for LoxError {
     fn from(vec_errs: Vec<ScanError>) -> LoxError {
         LoxError::ScanErrors(vec_errs)
@@ -140,3 +349,9 @@ impl From<Vec<ResolverError>> for LoxError {
         LoxError::ResolverErrors(vec_errs)
     }
 }
+
+impl From<Vec<TypeError>> for LoxError {
+    fn from(vec_errs: Vec<TypeError>) -> LoxError {
+        LoxError::TypeErrors(vec_errs)
+    }
+}
@@ -1,11 +1,14 @@
+use crate::stmt::FunDecl;
 use crate::token::Token;
 use crate::value;
 use std::fmt;
+use std::rc::Rc;
 
 // What can we do with an expr?
 #[derive(Debug)]
 pub enum Expr {
-    Literal(value::Value),
+    // the value and, when it was written in the source, its token
+    Literal(value::Value, Option<Token>),
     Variable(Token),
     This(Token),
     Super(Token, Token),
@@ -41,12 +44,21 @@ pub enum Expr {
         name: Token,
         value: Box<Expr>,
     },
+    // An anonymous function, written `fun (a) { ... }` or `(a) => ...`. The
+    // arrow form's body is a single return of its expression.
+    Lambda {
+        fun_decl: Rc<FunDecl>,
+        arrow: bool,
+    },
+    // A string with `${...}` in it: its text and expressions take turns,
+    // starting and ending with (maybe empty) text.
+    Interpolation(Vec<Expr>),
 }
 
 impl fmt::Display for Expr {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match &self {
-            Expr::Literal(value) => write!(f, "{}", value),
+            Expr::Literal(value, _) => write!(f, "{}", value),
             Expr::This(_) => write!(f, "this"),
             Expr::Super(_, method) => write!(f, "super.{}", method.lexeme),
             Expr::Variable(token) => write!(f, "v#{}", token.lexeme),
@@ -80,6 +92,17 @@ impl fmt::Display for Expr {
             } => {
                 write!(f, "(set {object} {} {value})", name.lexeme)
             }
+            Expr::Lambda { fun_decl, .. } => write!(f, "{fun_decl}"),
+            Expr::Interpolation(parts) => {
+                write!(f, "(interp")?;
+                for part in parts {
+                    match part {
+                        Expr::Literal(value::Value::VString(s), _) => write!(f, " {s:?}")?,
+                        part => write!(f, " {part}")?,
+                    }
+                }
+                write!(f, ")")
+            }
         }
     }
 }
@@ -92,16 +115,16 @@ mod tests {
 
     #[rstest::rstest]
     //#[case(Literal(Token{token: TokenType::Star, lexeme:"*", line: 1}), "*")]
-    #[case(Literal(value::Value::VNil), "nil")]
+    #[case(Literal(value::Value::VNil, None), "nil")]
     #[case(Binary{
         left: Box::new(
             Unary{
-                operator: Token{token: TokenType::Minus, lexeme: "-".into(), line: 1},
-                right: Box::new(Literal(value::Value::VNumber(123.0))),
+                operator: Token{token: TokenType::Minus, lexeme: "-".into(), line: 1, column: 1},
+                right: Box::new(Literal(value::Value::VNumber(123.0), None)),
             },
         ),
-        operator: Token{token: TokenType::Star, lexeme: "*".into(), line: 1},
-        right: Box::new(Grouping(Box::new(Literal(value::Value::VNumber(45.67))))),
+        operator: Token{token: TokenType::Star, lexeme: "*".into(), line: 1, column: 6},
+        right: Box::new(Grouping(Box::new(Literal(value::Value::VNumber(45.67), None)))),
     }, "(* (- 123) (group 45.67))")]
     fn test_display(#[case] expr: Expr, #[case] want: &str) {
         let got = format!("{}", expr);
@@ -1,9 +1,13 @@
 use crate::callable::LoxCallable;
+use crate::callable::LoxFunction;
+use crate::class::bind_property;
 use crate::class::GetSet;
 use crate::environment::Env;
 use crate::error::RuntimeError;
 use crate::interpreter::Interpreter;
 use crate::models::Expr;
+use crate::models::Token;
+use crate::models::TokenType;
 use crate::models::TokenType::*;
 use crate::models::Value;
 use std::rc::Rc;
@@ -16,7 +20,7 @@ impl Interpreter {
     // PUT THE LINE NUMBER ON EXPR
     fn priv_eval(&mut self, line: usize, expr: &Expr) -> Result<Value, RuntimeError> {
         match expr {
-            Expr::Literal(value) => Ok(value.clone()),
+            Expr::Literal(value, _) => Ok(value.clone()),
             Expr::Variable(token) => {
                 let name = &token.lexeme;
                 let expr_ptr = expr as *const Expr;
@@ -25,6 +29,7 @@ impl Interpreter {
                     None => self.globals.get(name),
                     Some(depth) => self.environment.get_at(name, *depth),
                 }
+                .map_err(|err| err.with_line(token.line))
             }
             Expr::This(token) => {
                 let name = &token.lexeme;
@@ -43,32 +48,47 @@ impl Interpreter {
                     Value::Class(lc) => lc,
                     _ => panic!("no super on non class"),
                 };
-                let object = match self.environment.get_at("this", depth - 1)? {
-                    Value::Object(inst) => inst,
+                let this = self.environment.get_at("this", depth - 1)?;
+                let found = match this {
+                    Value::Object(_) => parent.find_method(&method.lexeme),
+                    // in a static method
+                    Value::Class(_) => parent.find_class_method(&method.lexeme),
                     obj => panic!("no class defined here: {obj}"),
                 };
 
-                let method = parent
-                    .find_method(&method.lexeme)
-                    .expect("unresolved super method");
-                Ok(Value::Callable(Rc::new(method.bind(object))))
+                match found {
+                    Some(found) => bind_property(self, found, this),
+                    None => Err(RuntimeError::UndefinedProperty {
+                        line: method.line.to_string().into(),
+                        name: method.lexeme.clone(),
+                    }),
+                }
             }
             Expr::Assign { name, value } => {
-                let name = &name.lexeme;
                 let right = self.priv_eval(line, value)?;
                 let expr_ptr = expr as *const Expr;
                 let depth = self.resolutions.get(&expr_ptr);
                 match depth {
-                    None => self.globals.assign(name, right.clone())?,
-                    Some(depth) => self.environment.assign_at(name, right.clone(), *depth)?,
+                    None => self.globals.assign(&name.lexeme, right.clone()),
+                    Some(depth) => self
+                        .environment
+                        .assign_at(&name.lexeme, right.clone(), *depth),
                 }
+                .map_err(|err| err.with_line(name.line))?;
                 Ok(right)
             }
             Expr::Grouping(expr) => self.priv_eval(line, expr),
             Expr::Unary { operator, right } => {
                 let right = self.priv_eval(line, right)?;
+                let overloaded = match operator.token {
+                    Minus => self.overload(operator.line, "__neg__", &right, &[])?,
+                    _ => None,
+                };
+                if let Some(result) = overloaded {
+                    return Ok(result);
+                }
                 match operator.token {
-                    Minus => -right,
+                    Minus => (-right).map_err(|err| err.with_line(operator.line)),
                     Bang => Ok(Value::Bool(!bool::from(right))),
                     // ok to panic -- we should never parse a different unary op
                     _ => panic!("invalid unary operator '{}'", operator.lexeme),
@@ -95,17 +115,39 @@ impl Interpreter {
             } => {
                 let left = self.priv_eval(line, left)?;
                 let right = self.priv_eval(line, right)?;
+                if let Some(name) = special_method(&operator.token) {
+                    let args = [right.clone()];
+                    if let Some(result) = self.overload(operator.line, name, &left, &args)? {
+                        return Ok(result);
+                    }
+                }
+                let at_operator = |err: RuntimeError| err.with_line(operator.line);
                 match operator.token {
-                    Plus => left + right,
-                    Minus => left - right,
-                    Star => left * right,
-                    Slash => left / right,
-                    BangEqual => Ok(Value::Bool(left != right)),
-                    EqualEqual => Ok(Value::Bool(left == right)),
-                    Less => Ok(Value::Bool(left < right)),
-                    LessEqual => Ok(Value::Bool(left <= right)),
-                    Greater => Ok(Value::Bool(left > right)),
-                    GreaterEqual => Ok(Value::Bool(left >= right)),
+                    Plus => {
+                        let sum = (left + right).map_err(at_operator)?;
+                        if let Value::VString(s) = &sum {
+                            self.track_string(s.len())?;
+                        }
+                        Ok(sum)
+                    }
+                    Minus => (left - right).map_err(at_operator),
+                    Star => (left * right).map_err(at_operator),
+                    Slash => (left / right).map_err(at_operator),
+                    BangEqual => Ok(Value::Bool(!self.equals(operator, left, right)?)),
+                    EqualEqual => Ok(Value::Bool(self.equals(operator, left, right)?)),
+                    Is => match right {
+                        Value::Class(class) => Ok(Value::Bool(
+                            matches!(left, Value::Object(obj) if obj.class().inherits(&class)),
+                        )),
+                        Value::Trait(t) => Ok(Value::Bool(
+                            matches!(left, Value::Object(obj) if obj.class().uses(&t)),
+                        )),
+                        value => Err(RuntimeError::IsNotClass {
+                            line: operator.line.to_string().into(),
+                            value,
+                        }),
+                    },
+                    Less | LessEqual | Greater | GreaterEqual => left.compare(operator, &right),
                     // ok to panic -- we should never parse a different binary op
                     _ => panic!("invalid operation {operator}"),
                 }
@@ -121,7 +163,7 @@ impl Interpreter {
                         let arity = callee.arity();
                         if arity != arguments.len() {
                             return Err(RuntimeError::ArityMismatch {
-                                line: "TODO".into(),
+                                line: line.to_string().into(),
                                 want: arity,
                                 got: arguments.len(),
                             })?;
@@ -132,7 +174,7 @@ impl Interpreter {
                         let arity = class.arity();
                         if arity != arguments.len() {
                             return Err(RuntimeError::ArityMismatch {
-                                line: "TODO".into(),
+                                line: line.to_string().into(),
                                 want: arity,
                                 got: arguments.len(),
                             })?;
@@ -140,7 +182,7 @@ impl Interpreter {
                         Ok(class.call(self, arguments)?)
                     }
                     _ => Err(RuntimeError::NonCallableCalled {
-                        line: "TODO".into(),
+                        line: line.to_string().into(),
                         value: callee,
                     })?,
                 }
@@ -148,8 +190,19 @@ impl Interpreter {
             Expr::Get { object, name } => {
                 let lhs = self.priv_eval(line, object)?;
                 match lhs {
-                    Value::Object(obj) => obj.get(name),
-                    _ => panic!("invalid property access"),
+                    Value::Object(obj) => obj.get(self, name),
+                    Value::Class(class) => match class.find_class_method(&name.lexeme) {
+                        Some(method) => bind_property(self, method, Value::Class(class)),
+                        None => Err(RuntimeError::UndefinedProperty {
+                            line: name.line.to_string().into(),
+                            name: name.lexeme.clone(),
+                        }),
+                    },
+                    value => Err(RuntimeError::PropertyOnNonInstance {
+                        line: name.line.to_string().into(),
+                        name: name.lexeme.clone(),
+                        value,
+                    }),
                 }
             }
             Expr::Set {
@@ -164,11 +217,102 @@ impl Interpreter {
                         obj.set(name, rhs.clone());
                         Ok(rhs)
                     }
-                    _ => panic!("invalid property access"),
+                    value => Err(RuntimeError::FieldOnNonInstance {
+                        line: name.line.to_string().into(),
+                        name: name.lexeme.clone(),
+                        value,
+                    }),
+                }
+            }
+            Expr::Interpolation(parts) => {
+                let mut s = String::new();
+                for part in parts {
+                    let value = self.priv_eval(line, part)?;
+                    s.push_str(&self.stringify(line, value)?);
                 }
+                self.track_string(s.len())?;
+                Ok(Value::VString(s.into()))
+            }
+            Expr::Lambda { fun_decl, .. } => {
+                self.track_object()?;
+                Ok(Value::Callable(Rc::new(LoxFunction {
+                    definition: fun_decl.clone(),
+                    closure: self.environment.clone(),
+                    is_init: false,
+                    takes_this: false,
+                })))
+            }
+        }
+    }
+
+    // Instances may define what equality means for them with an `__eq__`
+    // method, or failing that an `equals` method, which is passed the right
+    // operand; `!=` is its negation.
+    fn equals(
+        &mut self,
+        operator: &Token,
+        left: Value,
+        right: Value,
+    ) -> Result<bool, RuntimeError> {
+        let args = [right];
+        for name in ["__eq__", "equals"] {
+            if let Some(result) = self.overload(operator.line, name, &left, &args)? {
+                return Ok(bool::from(result));
             }
         }
+        Ok(left == args[0])
+    }
+
+    // Calls a special method of an instance, e.g. to stand in for an
+    // operator, or returns None if it's not an instance with that method.
+    pub fn overload(
+        &mut self,
+        line: usize,
+        name: &str,
+        this: &Value,
+        args: &[Value],
+    ) -> Result<Option<Value>, RuntimeError> {
+        let Value::Object(obj) = this else {
+            return Ok(None);
+        };
+        let Some(method) = obj.class().find_method(name) else {
+            return Ok(None);
+        };
+        if method.arity() != args.len() {
+            return Err(RuntimeError::ArityMismatch {
+                line: line.to_string().into(),
+                want: method.arity(),
+                got: args.len(),
+            });
+        }
+        let result = method.bind(this.clone()).call(self, args.to_vec())?;
+        Ok(Some(result))
     }
+
+    // How `print` shows a value, which instances may choose with `__str__`.
+    pub fn stringify(&mut self, line: usize, value: Value) -> Result<String, RuntimeError> {
+        match self.overload(line, "__str__", &value, &[])? {
+            Some(shown) => Ok(shown.to_string()),
+            None => Ok(value.to_string()),
+        }
+    }
+}
+
+// The method an instance's class may define to take over a binary operator,
+// which is called on the left operand with the right one.
+fn special_method(operator: &TokenType) -> Option<&'static str> {
+    let name = match operator {
+        Plus => "__add__",
+        Minus => "__sub__",
+        Star => "__mul__",
+        Slash => "__div__",
+        Less => "__lt__",
+        LessEqual => "__le__",
+        Greater => "__gt__",
+        GreaterEqual => "__ge__",
+        _ => return None,
+    };
+    Some(name)
 }
 
 #[cfg(test)]
@@ -213,6 +357,13 @@ mod tests {
     #[case("true and true", Bool(true))]
     #[case("nil and hello", VNil)]
     #[case("nil or 17", VNumber(17.0))]
+    #[case("1 < 2", Bool(true))]
+    #[case("2 <= 1", Bool(false))]
+    #[case("\"b\" > \"a\"", Bool(true))]
+    #[case("\"a\" >= \"b\"", Bool(false))]
+    #[case("clock == clock", Bool(true))]
+    #[case("clock != clock", Bool(false))]
+    #[case("clock == nil", Bool(false))]
     fn test_eval(#[case] input: &str, #[case] want: Value) -> LoxResult<()> {
         //let mut env = Environment::default();
         let mut interpreter = Interpreter::default();
@@ -222,8 +373,39 @@ mod tests {
     }
 
     #[rstest::rstest]
-    #[case("4 + \"lox\"", "[line TODO] Error: type mismatch: 4 vs lox")]
-    #[case("2 + something", "[line TODO] Error: undefined variable: 'something'")]
+    #[case(
+        "4 + \"lox\"",
+        "[line 1] Error: operands of '+' must be two numbers or two strings, got number 4 and string \"lox\""
+    )]
+    #[case(
+        "-\"x\"",
+        "[line 1] Error: operand of '-' must be a number, got string \"x\""
+    )]
+    #[case(
+        "nil - 1",
+        "[line 1] Error: operands of '-' must be numbers, got nil and number 1"
+    )]
+    #[case(
+        "2 * true",
+        "[line 1] Error: operands of '*' must be numbers, got number 2 and boolean true"
+    )]
+    #[case(
+        "\"a\" / 2",
+        "[line 1] Error: operands of '/' must be numbers, got string \"a\" and number 2"
+    )]
+    #[case(
+        "1 <= \"1\"",
+        "[line 1] Error: operands of '<=' must be two numbers or two strings, got number 1 and string \"1\""
+    )]
+    #[case(
+        "clock > nil",
+        "[line 1] Error: operands of '>' must be two numbers or two strings, got function clock and nil"
+    )]
+    #[case(
+        "nil is clock",
+        "[line 1] Error: right operand of 'is' must be a class or trait, got clock"
+    )]
+    #[case("2 + something", "[line 1] Error: undefined variable: 'something'")]
     fn test_eval_error(#[case] input: &str, #[case] want: &str) -> LoxResult<()> {
         let mut interpreter = Interpreter::default();
         let got = str_eval(input, &mut interpreter).expect_err("should not evaluated");
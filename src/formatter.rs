@@ -0,0 +1,735 @@
+use crate::error::FormatError;
+use crate::error::LoxError;
+use crate::models::Comment;
+use crate::models::Expr;
+use crate::models::FunDecl;
+use crate::models::Stmt;
+use crate::models::StmtList;
+use crate::models::Token;
+use crate::models::Value;
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+const INDENT: &str = "  ";
+
+pub(crate) type Span = Option<(usize, usize)>;
+
+pub fn format_source(src: &str) -> Result<String, LoxError> {
+    let mut scanner = Scanner::new(src);
+    let tokens = scanner.scan_tokens()?;
+    let mut parser = Parser::new(&tokens);
+    let stmts = parser.parse()?;
+    Ok(Formatter::new(src, scanner.comments()).format(&stmts)?)
+}
+
+// Re-emits Lox source from a parsed tree. The tree only carries line numbers,
+// so comments are placed by line: anything before a statement is written on
+// its own line above it, anything on the statement's last line trails it.
+// Comments on its other lines go above it too, as the statement may be joined
+// into one line. Blank lines between statements are collapsed to at most one.
+pub struct Formatter<'a> {
+    comments: &'a [Comment],
+    blank_lines: BTreeSet<usize>,
+    out: String,
+    indent: usize,
+    last_line: Option<usize>,
+    block_start: bool,
+    // the first statement that failed to parse, if the tree is partial
+    unparsed: Option<usize>,
+}
+
+impl<'a> Formatter<'a> {
+    pub fn new(src: &str, comments: &'a [Comment]) -> Self {
+        let blank_lines = src
+            .lines()
+            .enumerate()
+            .filter(|(_, text)| text.trim().is_empty())
+            .map(|(i, _)| i + 1)
+            .collect();
+        Self {
+            comments,
+            blank_lines,
+            out: String::new(),
+            indent: 0,
+            last_line: None,
+            block_start: true,
+            unparsed: None,
+        }
+    }
+
+    pub fn format(mut self, stmts: &StmtList) -> Result<String, FormatError> {
+        self.stmt_list(stmts);
+        self.leading_comments(usize::MAX);
+        match self.unparsed {
+            Some(line) => Err(FormatError { line }),
+            None => Ok(self.out),
+        }
+    }
+
+    fn stmt_list(&mut self, stmts: &StmtList) {
+        for stmt in stmts {
+            self.item(stmt_span(stmt), |s| s.stmt(stmt));
+        }
+    }
+
+    // Writes one line-level item (a statement or a method) with the comments
+    // and blank lines that surround it in the source.
+    fn item(&mut self, span: Span, write_item: impl FnOnce(&mut Self)) {
+        if let Some((first, _)) = span {
+            self.leading_comments(first);
+            self.separate(first);
+        }
+        let start = self.out.len();
+        self.write_indent();
+        write_item(self);
+        match span {
+            Some((_, last)) => {
+                self.inner_comments(start, last);
+                self.trailing_comments(last);
+                self.last_line = Some(last);
+            }
+            // nothing to measure a gap from, e.g. an empty block
+            None => self.last_line = None,
+        }
+        self.out.push('\n');
+        self.block_start = false;
+    }
+
+    fn separate(&mut self, line: usize) {
+        if let Some(last_line) = self.last_line
+            && !self.block_start
+            && line > last_line + 1
+            && self.blank_lines.range(last_line + 1..line).next().is_some()
+        {
+            self.out.push('\n');
+        }
+    }
+
+    fn leading_comments(&mut self, before: usize) {
+        while let Some((comment, rest)) = self.comments.split_first()
+            && comment.line < before
+        {
+            self.comments = rest;
+            self.separate(comment.line);
+            self.write_indent();
+            self.out.push_str(&comment.text);
+            self.out.push('\n');
+            self.last_line = Some(comment.line);
+            self.block_start = false;
+        }
+    }
+
+    // Moves comments the item left unwritten, from lines before its last,
+    // above it, where `start` is.
+    fn inner_comments(&mut self, start: usize, last: usize) {
+        let mut hoisted = String::new();
+        while let Some((comment, rest)) = self.comments.split_first()
+            && comment.line < last
+        {
+            self.comments = rest;
+            for _ in 0..self.indent {
+                hoisted.push_str(INDENT);
+            }
+            hoisted.push_str(&comment.text);
+            hoisted.push('\n');
+        }
+        self.out.insert_str(start, &hoisted);
+    }
+
+    fn trailing_comments(&mut self, last: usize) {
+        while let Some((comment, rest)) = self.comments.split_first()
+            && comment.line <= last
+        {
+            self.comments = rest;
+            self.out.push(' ');
+            self.out.push_str(&comment.text);
+        }
+    }
+
+    fn write_indent(&mut self) {
+        for _ in 0..self.indent {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    fn block(&mut self, stmts: &StmtList) {
+        if stmts.stmts.is_empty() && !self.comments_before(stmts.end_line) {
+            self.out.push_str("{}");
+            return;
+        }
+        self.out.push_str("{\n");
+        self.indent += 1;
+        self.block_start = true;
+        self.stmt_list(stmts);
+        self.leading_comments(stmts.end_line);
+        self.indent -= 1;
+        self.write_indent();
+        self.out.push('}');
+    }
+
+    fn comments_before(&self, line: usize) -> bool {
+        self.comments.first().is_some_and(|c| c.line < line)
+    }
+
+    // Bodies of if/while/for stay on the same line as their header.
+    fn body(&mut self, stmt: &Stmt) {
+        self.out.push(' ');
+        self.stmt(stmt);
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Expr(_, expr) => {
+                self.expr(expr);
+                self.out.push(';');
+            }
+            Stmt::Print(_, expr) => {
+                self.out.push_str("print ");
+                self.expr(expr);
+                self.out.push(';');
+            }
+            Stmt::VarDecl(_, name, annotation, expr) => {
+                write!(self.out, "var {}", name.lexeme).expect("writes to a string");
+                self.annotation(annotation);
+                if let Some(expr) = expr {
+                    self.out.push_str(" = ");
+                    self.expr(expr);
+                }
+                self.out.push(';');
+            }
+            Stmt::FunDecl(fun_decl) => {
+                self.out.push_str("fun ");
+                self.function(fun_decl);
+            }
+            Stmt::Block(stmts) => match as_for(stmts) {
+                Some((init, cond, update, body)) => {
+                    self.out.push_str("for (");
+                    self.stmt(init);
+                    if !matches!(cond, Expr::Literal(Value::Bool(true), _)) {
+                        self.out.push(' ');
+                        self.expr(cond);
+                    }
+                    self.out.push_str("; ");
+                    self.expr(update);
+                    self.out.push(')');
+                    self.body(body);
+                }
+                None => self.block(stmts),
+            },
+            Stmt::IfThenElse {
+                if_expr,
+                then_stmt,
+                else_stmt,
+                ..
+            } => {
+                self.out.push_str("if (");
+                self.expr(if_expr);
+                self.out.push(')');
+                self.body(then_stmt);
+                if let Some(else_stmt) = else_stmt {
+                    if matches!(**then_stmt, Stmt::Block(_)) {
+                        self.out.push(' ');
+                    } else {
+                        self.out.push('\n');
+                        self.write_indent();
+                    }
+                    self.out.push_str("else");
+                    self.body(else_stmt);
+                }
+            }
+            Stmt::While(_, expr, body) => {
+                self.out.push_str("while (");
+                self.expr(expr);
+                self.out.push(')');
+                self.body(body);
+            }
+            Stmt::Return(_, expr) => match expr {
+                Expr::Literal(Value::VNil, _) => self.out.push_str("return;"),
+                expr => {
+                    self.out.push_str("return ");
+                    self.expr(expr);
+                    self.out.push(';');
+                }
+            },
+            Stmt::ClassDecl {
+                name,
+                parent,
+                traits,
+                methods,
+                end_line,
+                ..
+            } => {
+                write!(self.out, "class {}", name.lexeme).expect("writes to a string");
+                if let Some(parent) = parent {
+                    self.out.push_str(" < ");
+                    self.expr(parent);
+                }
+                for (i, t) in traits.iter().enumerate() {
+                    self.out.push_str(if i == 0 { " with " } else { ", " });
+                    self.expr(t);
+                }
+                self.methods(methods, *end_line);
+            }
+            Stmt::TraitDecl {
+                name,
+                methods,
+                end_line,
+                ..
+            } => {
+                write!(self.out, "trait {}", name.lexeme).expect("writes to a string");
+                self.methods(methods, *end_line);
+            }
+            Stmt::Error(line) => {
+                self.unparsed.get_or_insert(*line);
+            }
+        }
+    }
+
+    // The body of a class or trait.
+    fn methods(&mut self, methods: &[FunDecl], end_line: usize) {
+        if methods.is_empty() && !self.comments_before(end_line) {
+            self.out.push_str(" {}");
+            return;
+        }
+        self.out.push_str(" {\n");
+        self.indent += 1;
+        self.block_start = true;
+        for method in methods {
+            self.item(fun_span(method), |s| s.function(method));
+        }
+        self.leading_comments(end_line);
+        self.indent -= 1;
+        self.write_indent();
+        self.out.push('}');
+    }
+
+    fn function(&mut self, fun_decl: &FunDecl) {
+        if fun_decl.is_static {
+            self.out.push_str("class ");
+        }
+        self.out.push_str(&fun_decl.name.lexeme);
+        match fun_decl.is_getter {
+            true => self.annotation(&fun_decl.return_type),
+            false => self.signature(fun_decl),
+        }
+        self.out.push(' ');
+        self.block(&fun_decl.body);
+    }
+
+    fn signature(&mut self, fun_decl: &FunDecl) {
+        self.out.push('(');
+        for (i, parameter) in fun_decl.parameters.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.out.push_str(&parameter.lexeme);
+            self.annotation(&fun_decl.parameter_types[i]);
+        }
+        self.out.push(')');
+        self.annotation(&fun_decl.return_type);
+    }
+
+    fn annotation(&mut self, annotation: &Option<Token>) {
+        if let Some(annotation) = annotation {
+            write!(self.out, ": {}", annotation.lexeme).expect("writes to a string");
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Literal(Value::VString(s), _) => write!(self.out, "\"{}\"", escaped(s)),
+            // numbers keep the way they were written, e.g. `0xFF` or `1e300`
+            Expr::Literal(Value::VNumber(_), Some(token)) => {
+                self.out.push_str(&token.lexeme);
+                Ok(())
+            }
+            Expr::Literal(value, _) => write!(self.out, "{value}"),
+            Expr::Variable(token) | Expr::This(token) => write!(self.out, "{}", token.lexeme),
+            Expr::Super(_, method) => write!(self.out, "super.{}", method.lexeme),
+            Expr::Assign { name, value } => {
+                write!(self.out, "{} = ", name.lexeme).expect("writes to a string");
+                self.expr(value);
+                Ok(())
+            }
+            Expr::Grouping(expr) => {
+                self.out.push('(');
+                self.expr(expr);
+                self.out.push(')');
+                Ok(())
+            }
+            Expr::Unary { operator, right } => {
+                self.out.push_str(&operator.lexeme);
+                self.expr(right);
+                Ok(())
+            }
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            }
+            | Expr::Logical {
+                left,
+                operator,
+                right,
+            } => {
+                self.expr(left);
+                write!(self.out, " {} ", operator.lexeme).expect("writes to a string");
+                self.expr(right);
+                Ok(())
+            }
+            Expr::Call { callee, arguments } => {
+                self.expr(callee);
+                self.out.push('(');
+                for (i, argument) in arguments.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.expr(argument);
+                }
+                self.out.push(')');
+                Ok(())
+            }
+            Expr::Get { object, name } => {
+                self.expr(object);
+                write!(self.out, ".{}", name.lexeme)
+            }
+            Expr::Set {
+                object,
+                name,
+                value,
+            } => {
+                self.expr(object);
+                write!(self.out, ".{} = ", name.lexeme).expect("writes to a string");
+                self.expr(value);
+                Ok(())
+            }
+            Expr::Lambda { fun_decl, arrow } => {
+                match (arrow, fun_decl.body.stmts.as_slice()) {
+                    (true, [Stmt::Return(_, body)]) => {
+                        self.signature(fun_decl);
+                        self.out.push_str(" => ");
+                        self.expr(body);
+                    }
+                    _ => {
+                        self.out.push_str("fun ");
+                        self.signature(fun_decl);
+                        self.out.push(' ');
+                        self.block(&fun_decl.body);
+                    }
+                }
+                Ok(())
+            }
+            Expr::Interpolation(parts) => {
+                self.out.push('"');
+                for (i, part) in parts.iter().enumerate() {
+                    match (i % 2, part) {
+                        (0, Expr::Literal(Value::VString(s), _)) => self.out.push_str(&escaped(s)),
+                        _ => {
+                            self.out.push_str("${");
+                            self.expr(part);
+                            self.out.push('}');
+                        }
+                    }
+                }
+                self.out.push('"');
+                Ok(())
+            }
+        }
+        .expect("writes to a string")
+    }
+}
+
+// The parser desugars `for (init; cond; update) body` into
+// `{ init; while (cond) { body; update; } }`, so fold that shape back.
+// Loops without an initializer or an update stay as the `while` they became,
+// and hand-written blocks of the same shape stay as they are.
+fn as_for(stmts: &StmtList) -> Option<(&Stmt, &Expr, &Expr, &Stmt)> {
+    let [init @ (Stmt::VarDecl(..) | Stmt::Expr(..)), Stmt::While(_, cond, body)] =
+        stmts.stmts.as_slice()
+    else {
+        return None;
+    };
+    let Stmt::Block(inner) = &**body else {
+        return None;
+    };
+    if !stmts.from_for || !inner.from_for {
+        return None;
+    }
+    let [body, Stmt::Expr(_, update)] = inner.stmts.as_slice() else {
+        return None;
+    };
+    Some((init, cond, update, body))
+}
+
+fn merge(a: Span, b: Span) -> Span {
+    match (a, b) {
+        (Some((a0, a1)), Some((b0, b1))) => Some((a0.min(b0), a1.max(b1))),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+// The text of a string as it's written between its quotes. Newlines stay as
+// they are, so multiline strings keep their shape.
+fn escaped(s: &str) -> String {
+    let mut out = String::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            '\0' => out.push_str("\\0"),
+            '$' if chars.peek() == Some(&'{') => out.push_str("\\$"),
+            c if c.is_control() && c != '\n' => {
+                write!(out, "\\u{{{:x}}}", c as u32).expect("writes to a string")
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn at(line: usize) -> Span {
+    Some((line, line))
+}
+
+fn expr_span(expr: &Expr) -> Span {
+    match expr {
+        Expr::Literal(_, token) => token.as_ref().and_then(|token| at(token.line)),
+        Expr::Variable(token) | Expr::This(token) => at(token.line),
+        Expr::Super(token, method) => merge(at(token.line), at(method.line)),
+        Expr::Assign { name, value } => merge(at(name.line), expr_span(value)),
+        Expr::Grouping(expr) => expr_span(expr),
+        Expr::Unary { operator, right } => merge(at(operator.line), expr_span(right)),
+        Expr::Binary {
+            left,
+            operator,
+            right,
+        }
+        | Expr::Logical {
+            left,
+            operator,
+            right,
+        } => merge(merge(expr_span(left), at(operator.line)), expr_span(right)),
+        Expr::Call { callee, arguments } => arguments
+            .iter()
+            .fold(expr_span(callee), |span, arg| merge(span, expr_span(arg))),
+        Expr::Get { object, name } => merge(expr_span(object), at(name.line)),
+        Expr::Set {
+            object,
+            name,
+            value,
+        } => merge(merge(expr_span(object), at(name.line)), expr_span(value)),
+        Expr::Lambda { fun_decl, .. } => fun_span(fun_decl),
+        Expr::Interpolation(parts) => parts
+            .iter()
+            .fold(None, |span, part| merge(span, expr_span(part))),
+    }
+}
+
+// Runs through the closing '}', so comments on that line trail the block.
+fn list_span(stmts: &StmtList) -> Span {
+    stmts.into_iter().fold(at(stmts.end_line), |span, stmt| {
+        merge(span, stmt_span(stmt))
+    })
+}
+
+pub(crate) fn fun_span(fun_decl: &FunDecl) -> Span {
+    merge(at(fun_decl.line), list_span(&fun_decl.body))
+}
+
+pub(crate) fn stmt_span(stmt: &Stmt) -> Span {
+    match stmt {
+        Stmt::Expr(line, expr) | Stmt::Print(line, expr) | Stmt::Return(line, expr) => {
+            merge(at(*line), expr_span(expr))
+        }
+        Stmt::VarDecl(line, name, _, expr) => merge(
+            merge(at(*line), at(name.line)),
+            expr.as_ref().and_then(expr_span),
+        ),
+        Stmt::FunDecl(fun_decl) => fun_span(fun_decl),
+        Stmt::Block(stmts) => list_span(stmts),
+        Stmt::IfThenElse {
+            line,
+            if_expr,
+            then_stmt,
+            else_stmt,
+        } => merge(
+            merge(at(*line), expr_span(if_expr)),
+            merge(
+                stmt_span(then_stmt),
+                else_stmt.as_deref().and_then(stmt_span),
+            ),
+        ),
+        Stmt::While(line, expr, body) => merge(merge(at(*line), expr_span(expr)), stmt_span(body)),
+        Stmt::ClassDecl {
+            line,
+            name,
+            parent,
+            traits,
+            methods,
+            end_line,
+        } => methods.iter().fold(
+            traits.iter().fold(
+                merge(
+                    merge(at(*line), at(*end_line)),
+                    merge(at(name.line), parent.as_ref().and_then(expr_span)),
+                ),
+                |span, t| merge(span, expr_span(t)),
+            ),
+            |span, method| merge(span, fun_span(method)),
+        ),
+        Stmt::TraitDecl {
+            line,
+            methods,
+            end_line,
+            ..
+        } => methods
+            .iter()
+            .fold(merge(at(*line), at(*end_line)), |span, method| {
+                merge(span, fun_span(method))
+            }),
+        Stmt::Error(line) => at(*line),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[rstest::rstest]
+    #[case("print   1+2 ;", "print 1 + 2;\n")]
+    #[case("print 0xFF+0b1010;", "print 0xFF + 0b1010;\n")]
+    #[case("var big=1_000_000*1e300;", "var big = 1_000_000 * 1e300;\n")]
+    #[case("print 2.5E-3;", "print 2.5E-3;\n")]
+    #[case("var x=(1+2)*-3;var y;", "var x = (1 + 2) * -3;\nvar y;\n")]
+    #[case("a.b.c=d(1,\"two\",nil)  ;", "a.b.c = d(1, \"two\", nil);\n")]
+    #[case("if(a)print 1;else print 2;", "if (a) print 1;\nelse print 2;\n")]
+    #[case(
+        "if (a) { print 1; } else if (b) {} else { print 3; }",
+        "if (a) {\n  print 1;\n} else if (b) {} else {\n  print 3;\n}\n"
+    )]
+    #[case(
+        "while(true and !false){x=x+1;}",
+        "while (true and !false) {\n  x = x + 1;\n}\n"
+    )]
+    #[case(
+        "for (var i = 0; i < 3; i = i + 1) print i;",
+        "for (var i = 0; i < 3; i = i + 1) print i;\n"
+    )]
+    #[case("for (i = 0;; i = i + 1) {}", "for (i = 0;; i = i + 1) {}\n")]
+    #[case("for (;;) {}", "while (true) {}\n")]
+    #[case(
+        "{ var i = 0; while (i < 3) { print i; i = i + 1; } }",
+        "{\n  var i = 0;\n  while (i < 3) {\n    print i;\n    i = i + 1;\n  }\n}\n"
+    )]
+    #[case(
+        "fun f(a,b){return a+b;} fun g(){return;}",
+        "fun f(a, b) {\n  return a + b;\n}\nfun g() {\n  return;\n}\n"
+    )]
+    #[case(
+        "class A<B{init(x){this.x=x;} get(){return super.get();}}",
+        "class A < B {\n  init(x) {\n    this.x = x;\n  }\n  get() {\n    return super.get();\n  }\n}\n"
+    )]
+    #[case("class A {}", "class A {}\n")]
+    #[case(
+        "print \"\\\"q\\\" \\\\ \\t \\u{7}\\u{e9}\";\nprint \"\"\"raw \\\"\nnext\"\"\";",
+        "print \"\\\"q\\\" \\\\ \\t \\u{7}é\";\nprint \"raw \\\\\\\"\nnext\";\n"
+    )]
+    #[case(
+        r#"print "x=${x+1}, \${y}"+"\${";"#,
+        "print \"x=${x + 1}, \\${y}\" + \"\\${\";\n"
+    )]
+    #[case(
+        "trait T{f(){return this;}}class A<B with T,U{}",
+        "trait T {\n  f() {\n    return this;\n  }\n}\nclass A < B with T, U {}\n"
+    )]
+    #[case(
+        "class M{class sq(n){return n*n;} pi:num{return 3;}}",
+        "class M {\n  class sq(n) {\n    return n * n;\n  }\n  pi: num {\n    return 3;\n  }\n}\n"
+    )]
+    #[case(
+        "var f=fun(a,b){return a+b;};\nprint map((x)=>x*2,fun(){});",
+        "var f = fun (a, b) {\n  return a + b;\n};\nprint map((x) => x * 2, fun () {});\n"
+    )]
+    #[case(
+        "var x:num=1;fun f(a:num,b):str{return \"\";}",
+        "var x: num = 1;\nfun f(a: num, b): str {\n  return \"\";\n}\n"
+    )]
+    #[case(
+        "while (x) {\n  x = x - 1;\n}\nprint x;\n\nprint x;",
+        "while (x) {\n  x = x - 1;\n}\nprint x;\n\nprint x;\n"
+    )]
+    fn test_format(#[case] input: &str, #[case] want: &str) -> Result<(), LoxError> {
+        let got = format_source(input)?;
+        assert_eq!(got, want);
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case(
+        "// header\n\n\n\nvar a = 1; // one\n// about b\nvar b = 2;\n\n// footer\n",
+        "// header\n\nvar a = 1; // one\n// about b\nvar b = 2;\n\n// footer\n"
+    )]
+    #[case(
+        "fun f() {\n    // inside\n    print 1;    // trailing\n}\n",
+        "fun f() {\n  // inside\n  print 1; // trailing\n}\n"
+    )]
+    #[case(
+        "fun f() {\n  print 1;\n  // end of f\n}",
+        "fun f() {\n  print 1;\n  // end of f\n}\n"
+    )]
+    #[case("if (x) {\n  // todo\n}", "if (x) {\n  // todo\n}\n")]
+    #[case(
+        "class A {\n  m() {}\n\n  // more to come\n} // A\nprint 1;",
+        "class A {\n  m() {}\n\n  // more to come\n} // A\nprint 1;\n"
+    )]
+    #[case("trait T { // empty\n}", "trait T {\n  // empty\n}\n")]
+    #[case(
+        "var x = 1 + // one\n  2 // two\n  ;",
+        "// one\nvar x = 1 + 2; // two\n"
+    )]
+    #[case(
+        "{\n  print f(1, // one\n    2); // two\n}",
+        "{\n  // one\n  print f(1, 2); // two\n}\n"
+    )]
+    #[case(
+        "if (a // one\n  and b) print 1; // two",
+        "// one\nif (a and b) print 1; // two\n"
+    )]
+    fn test_format_comments(#[case] input: &str, #[case] want: &str) -> Result<(), LoxError> {
+        let got = format_source(input)?;
+        assert_eq!(got, want);
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_partial_parse() -> Result<(), LoxError> {
+        let src = "print 1;\n{\n  print ;\n}";
+        let mut scanner = Scanner::new(src);
+        let tokens = scanner.scan_tokens()?;
+        let (stmts, _) = Parser::new(&tokens).parse_partial();
+        let got = Formatter::new(src, scanner.comments()).format(&stmts);
+        assert_eq!(got, Err(FormatError { line: 3 }));
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case(include_str!("../testdata/fib.lox"))]
+    #[case(include_str!("../testdata/fib_slow.lox"))]
+    #[case(include_str!("../testdata/fib_timer.lox"))]
+    #[case(include_str!("../testdata/line.lox"))]
+    #[case(include_str!("../testdata/loop.lox"))]
+    #[case(include_str!("../testdata/scope.lox"))]
+    #[case("var x = 1 + // one\n  2 // two\n  ;\nprint x; // three")]
+    fn test_format_idempotent(#[case] input: &str) -> Result<(), LoxError> {
+        let once = format_source(input)?;
+        let twice = format_source(&once)?;
+        assert_eq!(once, twice);
+        Ok(())
+    }
+}
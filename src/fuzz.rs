@@ -0,0 +1,522 @@
+use crate::checker::Checker;
+use crate::error::LoxError;
+use crate::error::LoxResult;
+use crate::formatter::format_source;
+use crate::interpreter::Hook;
+use crate::interpreter::Interpreter;
+use crate::limits::Limits;
+use crate::output::SharedBuffer;
+use crate::parser::Parser;
+use crate::profiler::Profiler;
+use crate::resolver::Resolver;
+use crate::scanner::Scanner;
+use std::fmt::Write;
+use thiserror::Error;
+
+// how deeply blocks, and separately expressions, nest
+const MAX_DEPTH: usize = 3;
+const MAX_STATEMENTS: usize = 64;
+
+// Generated programs may loop or recurse forever, so they run under limits.
+// There is no timeout, which would make outcomes depend on the machine.
+fn limits() -> Limits {
+    Limits {
+        max_steps: Some(10_000),
+        max_depth: Some(32),
+        max_objects: Some(1_000),
+        max_string_bytes: Some(1 << 16),
+        timeout: None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Value,
+    // a loop counter, which can be read but not assigned
+    Counter,
+    Function(usize),
+    Class,
+}
+
+// Builds a well-formed Lox program from arbitrary bytes, so a fuzzer's
+// mutations turn into programs that get past the parser and resolver. Each
+// choice reads a byte, and once they run out every choice is the first one,
+// which never recurses, so any input gives a finite program.
+pub fn generate(data: &[u8]) -> String {
+    let mut generator = Generator {
+        data,
+        pos: 0,
+        out: String::new(),
+        indent: 0,
+        depth: 0,
+        scopes: vec![vec![]],
+        in_function: false,
+        in_method: false,
+        next_name: 0,
+    };
+    let mut count = 0;
+    while generator.pos < data.len() && count < MAX_STATEMENTS {
+        generator.statement();
+        count += 1;
+    }
+    generator.out
+}
+
+struct Generator<'a> {
+    data: &'a [u8],
+    pos: usize,
+    out: String,
+    indent: usize,
+    // block nesting
+    depth: usize,
+    // names declared so far, innermost scope last
+    scopes: Vec<Vec<(String, Kind)>>,
+    // whether return is allowed
+    in_function: bool,
+    // whether `this` is an instance with a `value` field
+    in_method: bool,
+    next_name: usize,
+}
+
+impl Generator<'_> {
+    fn choose(&mut self, n: usize) -> usize {
+        let byte = self.data.get(self.pos).copied().unwrap_or(0);
+        self.pos += 1;
+        byte as usize % n
+    }
+
+    fn fresh(&mut self, prefix: &str) -> String {
+        self.next_name += 1;
+        format!("{prefix}{}", self.next_name)
+    }
+
+    fn declare(&mut self, name: &str, kind: Kind) {
+        self.scopes
+            .last_mut()
+            .expect("there is always a scope")
+            .push((name.to_owned(), kind));
+    }
+
+    // Picks a name in scope for which `wanted` holds.
+    fn pick(&mut self, wanted: impl Fn(Kind) -> bool) -> Option<(String, Kind)> {
+        let names: Vec<_> = self
+            .scopes
+            .iter()
+            .flatten()
+            .filter(|(_, kind)| wanted(*kind))
+            .cloned()
+            .collect();
+        if names.is_empty() {
+            return None;
+        }
+        let i = self.choose(names.len());
+        Some(names[i].clone())
+    }
+
+    fn line(&mut self, text: &str) {
+        for _ in 0..self.indent {
+            self.out.push_str("  ");
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn open(&mut self, head: &str) {
+        match head {
+            "" => self.line("{"),
+            _ => self.line(&format!("{head} {{")),
+        }
+        self.indent += 1;
+        self.depth += 1;
+        self.scopes.push(vec![]);
+    }
+
+    fn close(&mut self) {
+        self.scopes.pop();
+        self.depth -= 1;
+        self.indent -= 1;
+        self.line("}");
+    }
+
+    // The statements of a block that has just been opened.
+    fn body(&mut self) {
+        for _ in 0..self.choose(3) {
+            self.statement();
+        }
+    }
+
+    fn statement(&mut self) {
+        let choices = if self.depth < MAX_DEPTH { 10 } else { 4 };
+        match self.choose(choices) {
+            0 => {
+                let value = self.expr(0);
+                self.line(&format!("print {value};"));
+            }
+            1 => {
+                let value = self.expr(0);
+                let name = self.fresh("v");
+                self.line(&format!("var {name} = {value};"));
+                self.declare(&name, Kind::Value);
+            }
+            // functions and classes are never reassigned, so calls and
+            // superclasses stay well-formed
+            2 => match self.pick(|kind| kind == Kind::Value) {
+                Some((name, _)) => {
+                    let value = self.expr(0);
+                    self.line(&format!("{name} = {value};"));
+                }
+                None => self.line("print nil;"),
+            },
+            3 => match self.pick(|kind| matches!(kind, Kind::Function(_))) {
+                Some((name, Kind::Function(arity))) => {
+                    let args = self.args(arity, 0);
+                    self.line(&format!("{name}({args});"));
+                }
+                _ if self.in_method => {
+                    let value = self.expr(0);
+                    self.line(&format!("this.value = {value};"));
+                }
+                _ => self.line("print true;"),
+            },
+            4 => {
+                let cond = self.expr(0);
+                self.open(&format!("if ({cond})"));
+                self.body();
+                self.close();
+                if self.choose(2) == 1 {
+                    self.open("else");
+                    self.body();
+                    self.close();
+                }
+            }
+            5 => {
+                let counter = self.fresh("i");
+                let times = self.choose(4);
+                self.open(&format!(
+                    "for (var {counter} = 0; {counter} < {times}; {counter} = {counter} + 1)"
+                ));
+                self.declare(&counter, Kind::Counter);
+                self.body();
+                self.close();
+            }
+            6 => {
+                self.open("");
+                self.body();
+                self.close();
+            }
+            7 => self.function(),
+            8 => self.class(),
+            _ if self.in_function => {
+                let value = self.expr(0);
+                self.line(&format!("return {value};"));
+            }
+            _ => {
+                let value = self.expr(0);
+                self.line(&format!("{value};"));
+            }
+        }
+    }
+
+    // A function body, ending with a return so calls usually produce values.
+    fn function_body(&mut self) {
+        self.body();
+        let value = self.expr(0);
+        self.line(&format!("return {value};"));
+    }
+
+    fn function(&mut self) {
+        let name = self.fresh("f");
+        let arity = self.choose(3);
+        let params: Vec<_> = (0..arity).map(|_| self.fresh("p")).collect();
+        if self.choose(3) == 0 {
+            return self.arrow(&name, arity, &params);
+        }
+        // declared first, so it may call itself
+        self.declare(&name, Kind::Function(arity));
+        let in_function = std::mem::replace(&mut self.in_function, true);
+        self.open(&format!("fun {name}({})", params.join(", ")));
+        for param in &params {
+            self.declare(param, Kind::Value);
+        }
+        self.function_body();
+        self.close();
+        self.in_function = in_function;
+    }
+
+    // An arrow function bound to a variable, which the resolver won't let
+    // its body see, so it's declared after.
+    fn arrow(&mut self, name: &str, arity: usize, params: &[String]) {
+        self.scopes.push(
+            params
+                .iter()
+                .map(|param| (param.clone(), Kind::Value))
+                .collect(),
+        );
+        let body = self.expr(0);
+        self.scopes.pop();
+        self.line(&format!("var {name} = ({}) => {body};", params.join(", ")));
+        self.declare(name, Kind::Function(arity));
+    }
+
+    fn class(&mut self) {
+        let name = self.fresh("C");
+        let superclass = match self.choose(2) {
+            0 => None,
+            _ => self.pick(|kind| kind == Kind::Class).map(|(name, _)| name),
+        };
+        let head = match &superclass {
+            Some(superclass) => format!("class {name} < {superclass}"),
+            None => format!("class {name}"),
+        };
+        self.declare(&name, Kind::Class);
+        self.open(&head);
+        if superclass.is_none() {
+            self.line("init(value) {");
+            self.line("  this.value = value;");
+            self.line("}");
+        }
+
+        let in_function = std::mem::replace(&mut self.in_function, true);
+        let in_method = std::mem::replace(&mut self.in_method, true);
+        let param = self.fresh("p");
+        self.open(&format!("get({param})"));
+        self.declare(&param, Kind::Value);
+        if superclass.is_some() && self.choose(2) == 0 {
+            let value = self.expr(0);
+            self.line(&format!("return super.get({param}) + {value};"));
+        } else {
+            self.function_body();
+        }
+        self.close();
+        self.in_function = in_function;
+        self.in_method = in_method;
+        self.close();
+    }
+
+    fn args(&mut self, arity: usize, depth: usize) -> String {
+        let args: Vec<_> = (0..arity).map(|_| self.expr(depth + 1)).collect();
+        args.join(", ")
+    }
+
+    fn expr(&mut self, depth: usize) -> String {
+        if depth >= MAX_DEPTH {
+            return self.leaf();
+        }
+        match self.choose(7) {
+            0 => self.leaf(),
+            1 => {
+                const OPERATORS: &[&str] = &[
+                    "+", "-", "*", "/", "<", "<=", ">", ">=", "==", "!=", "and", "or",
+                ];
+                let operator = OPERATORS[self.choose(OPERATORS.len())];
+                let lhs = self.expr(depth + 1);
+                let rhs = self.expr(depth + 1);
+                format!("({lhs} {operator} {rhs})")
+            }
+            2 => {
+                let operator = ["-", "!"][self.choose(2)];
+                let operand = self.expr(depth + 1);
+                format!("({operator}{operand})")
+            }
+            3 => match self.pick(|kind| matches!(kind, Kind::Function(_))) {
+                Some((name, Kind::Function(arity))) => {
+                    let args = self.args(arity, depth);
+                    format!("{name}({args})")
+                }
+                _ => self.leaf(),
+            },
+            4 => match self.pick(|kind| kind == Kind::Class) {
+                Some((name, _)) => {
+                    let value = self.expr(depth + 1);
+                    if self.choose(2) == 0 {
+                        format!("{name}({value}).value")
+                    } else {
+                        let arg = self.expr(depth + 1);
+                        format!("{name}({value}).get({arg})")
+                    }
+                }
+                None => self.leaf(),
+            },
+            5 if self.in_method => "this.value".to_owned(),
+            _ => match self.pick(|kind| kind != Kind::Class) {
+                Some((name, _)) => name,
+                None => self.leaf(),
+            },
+        }
+    }
+
+    fn leaf(&mut self) -> String {
+        match self.choose(6) {
+            0 => "nil".to_owned(),
+            1 => "true".to_owned(),
+            2 => "false".to_owned(),
+            3 => {
+                let mut number = self.choose(10).to_string();
+                if self.choose(2) == 0 {
+                    write!(&mut number, ".{}", self.choose(10))
+                        .expect("must be able to append to string");
+                }
+                number
+            }
+            4 => ["\"\"", "\"a\"", "\"lox\""][self.choose(3)].to_owned(),
+            _ => match self.pick(|kind| matches!(kind, Kind::Value | Kind::Counter)) {
+                Some((name, _)) => name,
+                None => "0".to_owned(),
+            },
+        }
+    }
+}
+
+// What a program printed, and how it failed if it did. Errors are kept
+// without their location, since formatting moves code between lines.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Outcome {
+    pub stdout: String,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum FuzzError {
+    #[error("the program doesn't compile: {0}")]
+    Invalid(String),
+    #[error("{backend} diverged: want {want:?}, got {got:?}")]
+    Diverged {
+        backend: &'static str,
+        want: Outcome,
+        got: Outcome,
+    },
+}
+
+fn interpret(interpreter: &mut Interpreter, src: &str) -> LoxResult<()> {
+    let mut scanner = Scanner::new(src);
+    let tokens = scanner.scan_tokens()?;
+    let mut parser = Parser::new(&tokens);
+    let stmts = parser.parse()?;
+    let mut resolver = Resolver::default();
+    interpreter.resolutions = resolver.resolve(&stmts)?;
+    Checker::default().check(&stmts)?;
+    Ok(interpreter.interpret(&stmts)?)
+}
+
+// Runs a program, returning compile errors and keeping runtime ones in the
+// outcome.
+fn run(src: &str, hook: Option<Box<dyn Hook>>) -> LoxResult<Outcome> {
+    let stdout = SharedBuffer::default();
+    let mut interpreter = Interpreter::builder()
+        .allow_stdout()
+        .stdout(stdout.clone())
+        .limits(limits())
+        .build();
+    interpreter.hook = hook;
+    let error = match interpret(&mut interpreter, src) {
+        Ok(()) => None,
+        Err(LoxError::RuntimeError(err)) => {
+            let msg = err.to_string();
+            Some(match msg.split_once("Error: ") {
+                Some((_, msg)) => msg.to_owned(),
+                None => msg,
+            })
+        }
+        Err(err) => return Err(err),
+    };
+    Ok(Outcome {
+        stdout: stdout.contents(),
+        error,
+    })
+}
+
+// Runs a program through the interpreter and the other ways we have of
+// running it, which must all agree: formatting it first, and running it
+// under the profiler's hook. Panics are left for the fuzzer to catch.
+pub fn check(src: &str) -> Result<Outcome, FuzzError> {
+    let want = run(src, None).map_err(|err| FuzzError::Invalid(err.to_string()))?;
+    let diverged = |backend, got: LoxResult<Outcome>| {
+        let got = got.unwrap_or_else(|err| Outcome {
+            stdout: String::new(),
+            error: Some(err.to_string()),
+        });
+        if got == want {
+            Ok(())
+        } else {
+            Err(FuzzError::Diverged {
+                backend,
+                want: want.clone(),
+                got,
+            })
+        }
+    };
+    diverged(
+        "formatted",
+        format_source(src).and_then(|src| run(&src, None)),
+    )?;
+    diverged("profiled", run(src, Some(Box::new(Profiler::default()))))?;
+    Ok(want)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    // Deterministic bytes standing in for a fuzzer's input.
+    fn bytes(seed: u64, len: usize) -> Vec<u8> {
+        let mut state = seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_generate_empty() {
+        assert_eq!(generate(&[]), "");
+    }
+
+    #[test]
+    fn test_generate_deterministic() {
+        let data = bytes(7, 128);
+        assert_eq!(generate(&data), generate(&data));
+    }
+
+    #[test]
+    fn test_generated_programs_agree() {
+        // nested calls take more stack than a test thread gets in debug builds
+        thread::Builder::new()
+            .stack_size(64 << 20)
+            .spawn(|| {
+                for seed in 0..200 {
+                    let src = generate(&bytes(seed, 256));
+                    if let Err(err) = check(&src) {
+                        panic!("seed {seed}: {err}\n{src}");
+                    }
+                }
+            })
+            .expect("must be able to spawn")
+            .join()
+            .expect("generated programs should agree");
+    }
+
+    #[rstest::rstest]
+    #[case("print 1 + 2;", "3\n", None)]
+    #[case("print 1;\nprint nope;", "1\n", Some("undefined variable: 'nope'"))]
+    fn test_check(#[case] src: &str, #[case] stdout: &str, #[case] error: Option<&str>) {
+        let got = check(src).expect("should agree");
+        assert_eq!(
+            got,
+            Outcome {
+                stdout: stdout.to_owned(),
+                error: error.map(str::to_owned),
+            }
+        );
+    }
+
+    #[test]
+    fn test_check_invalid() {
+        let err = check("print ;").expect_err("should not compile");
+        assert!(matches!(err, FuzzError::Invalid(_)), "{err}");
+    }
+}
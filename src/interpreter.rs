@@ -1,59 +1,225 @@
-use crate::callable::Clock;
-use crate::environment::Env;
+use crate::capabilities::Capabilities;
 use crate::environment::Environment;
 use crate::error::RuntimeError;
+use crate::limits::Limits;
+use crate::limits::Usage;
 use crate::models::Expr;
+use crate::models::Stmt;
 use crate::models::StmtList;
-use crate::models::Value;
+use crate::natives::define_natives;
+use compact_str::CompactString;
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::io;
+use std::io::Write;
+use std::path::PathBuf;
 use std::rc::Rc;
 
 pub struct Interpreter {
     pub globals: Rc<Environment>,
     pub environment: Rc<Environment>,
-    pub buffer: Vec<u8>,
     pub resolutions: HashMap<*const Expr, usize>,
+    // innermost call last; the script itself is the first frame
+    pub frames: Vec<Frame>,
+    pub hook: Option<Box<dyn Hook>>,
+    pub limits: Limits,
+    pub usage: Usage,
+    pub capabilities: Capabilities,
+    // where `print` writes
+    pub stdout: Box<dyn Write>,
+    // for hosts reporting errors alongside the script's output
+    pub stderr: Box<dyn Write>,
 }
 
+// Builds an interpreter that may only touch what it is explicitly allowed to.
+#[derive(Default)]
+pub struct InterpreterBuilder {
+    capabilities: Capabilities,
+    limits: Limits,
+    stdout: Option<Box<dyn Write>>,
+    stderr: Option<Box<dyn Write>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub function: CompactString,
+    // the line this frame is currently executing
+    pub line: usize,
+}
+
+// Lets tools such as the debugger watch statements execute. The hook is taken
+// out of the interpreter while it runs, so whatever it evaluates isn't traced.
+pub trait Hook {
+    fn statement(
+        &mut self,
+        interpreter: &mut Interpreter,
+        line: usize,
+        stmt: &Stmt,
+    ) -> Result<(), RuntimeError>;
+
+    // after a function's frame is pushed
+    fn enter(&mut self, _interpreter: &Interpreter) {}
+
+    // before a function's frame is popped
+    fn leave(&mut self, _interpreter: &Interpreter) {}
+}
+
+// Lets the caller keep a handle on a hook to read back what it collected.
+impl<H: Hook> Hook for Rc<RefCell<H>> {
+    fn statement(
+        &mut self,
+        interpreter: &mut Interpreter,
+        line: usize,
+        stmt: &Stmt,
+    ) -> Result<(), RuntimeError> {
+        self.borrow_mut().statement(interpreter, line, stmt)
+    }
+
+    fn enter(&mut self, interpreter: &Interpreter) {
+        self.borrow_mut().enter(interpreter)
+    }
+
+    fn leave(&mut self, interpreter: &Interpreter) {
+        self.borrow_mut().leave(interpreter)
+    }
+}
+
+// Allowed to print and read the clock, like scripts always were; use the
+// builder for anything else.
 impl Default for Interpreter {
     fn default() -> Self {
-        let clock = Rc::new(Clock);
         let mut def = Self {
             globals: Default::default(),
             environment: Default::default(),
-            buffer: Default::default(),
             resolutions: Default::default(),
+            frames: vec![Frame {
+                function: "script".into(),
+                line: 0,
+            }],
+            hook: None,
+            limits: Default::default(),
+            usage: Default::default(),
+            capabilities: Capabilities {
+                stdout: true,
+                time: true,
+                ..Default::default()
+            },
+            stdout: Box::new(io::stdout()),
+            stderr: Box::new(io::stderr()),
         };
         def.environment = def.globals.clone();
-        def.environment.define("clock", Value::Callable(clock));
+        define_natives(&def.globals);
         def
     }
 }
-#[cfg(test)]
-impl io::Write for Interpreter {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.buffer.write(buf)
+impl InterpreterBuilder {
+    pub fn allow_read(mut self, path: impl Into<PathBuf>) -> Self {
+        self.capabilities.read.push(path.into());
+        self
     }
 
-    fn flush(&mut self) -> io::Result<()> {
-        self.buffer.flush()
+    pub fn allow_write(mut self, path: impl Into<PathBuf>) -> Self {
+        self.capabilities.write.push(path.into());
+        self
+    }
+
+    pub fn allow_env(mut self) -> Self {
+        self.capabilities.env = true;
+        self
+    }
+
+    pub fn allow_time(mut self) -> Self {
+        self.capabilities.time = true;
+        self
     }
-}
 
-#[cfg(not(test))]
-impl io::Write for Interpreter {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        io::stdout().write(buf)
+    pub fn allow_stdout(mut self) -> Self {
+        self.capabilities.stdout = true;
+        self
     }
 
-    fn flush(&mut self) -> io::Result<()> {
-        io::stdout().flush()
+    pub fn allow_all(mut self) -> Self {
+        self.capabilities = Capabilities::all();
+        self
+    }
+
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn stdout(mut self, stdout: impl Write + 'static) -> Self {
+        self.stdout = Some(Box::new(stdout));
+        self
+    }
+
+    pub fn stderr(mut self, stderr: impl Write + 'static) -> Self {
+        self.stderr = Some(Box::new(stderr));
+        self
+    }
+
+    pub fn build(self) -> Interpreter {
+        let mut interpreter = Interpreter {
+            capabilities: self.capabilities,
+            limits: self.limits,
+            ..Default::default()
+        };
+        if let Some(stdout) = self.stdout {
+            interpreter.stdout = stdout;
+        }
+        if let Some(stderr) = self.stderr {
+            interpreter.stderr = stderr;
+        }
+        interpreter
     }
 }
 
 impl Interpreter {
+    pub fn builder() -> InterpreterBuilder {
+        InterpreterBuilder::default()
+    }
+
+    // Runs a script, or one line of one; steps and the timeout start over
+    // with each run.
     pub fn interpret(&mut self, stmts: &StmtList) -> Result<(), RuntimeError> {
+        self.usage.start_run();
+        self.execute(stmts)
+    }
+
+    pub(crate) fn execute(&mut self, stmts: &StmtList) -> Result<(), RuntimeError> {
         stmts.into_iter().try_for_each(|s| self.eval(s))
     }
+
+    pub(crate) fn enter(&mut self, frame: Frame) -> Result<(), RuntimeError> {
+        self.check_depth()?;
+        self.frames.push(frame);
+        if let Some(mut hook) = self.hook.take() {
+            hook.enter(self);
+            self.hook = Some(hook);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn leave(&mut self) {
+        if let Some(mut hook) = self.hook.take() {
+            hook.leave(self);
+            self.hook = Some(hook);
+        }
+        self.frames.pop();
+    }
+
+    pub(crate) fn trace(&mut self, line: usize, stmt: &Stmt) -> Result<(), RuntimeError> {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.line = line;
+        }
+        self.tick()?;
+        match self.hook.take() {
+            None => Ok(()),
+            Some(mut hook) => {
+                let result = hook.statement(self, line, stmt);
+                self.hook = Some(hook);
+                result
+            }
+        }
+    }
 }
@@ -0,0 +1,229 @@
+use crate::error::LoxError;
+use crate::error::ParseError;
+use crate::error::RuntimeError;
+use crate::models::Token;
+use crate::models::TokenType;
+use crate::resolver::ResolverError;
+use std::fmt::Write;
+
+// Reports errors exactly the way jlox, the reference implementation from
+// Crafting Interpreters, does, so we can run its test suite. Our own messages
+// carry more detail; this maps them back onto the book's wording.
+pub fn report(err: &LoxError) -> String {
+    let mut lines = vec![];
+    match err {
+        LoxError::ScanErrors(errs) => {
+            for err in errs {
+                // ours name the offending character, e.g. "Unexpected character: @"
+                let msg = err
+                    .msg
+                    .split_once(':')
+                    .map_or(err.msg.as_str(), |(msg, _)| msg);
+                let msg = msg.trim_end_matches('.');
+                lines.push(format!("[line {}] Error: {msg}.", err.line));
+            }
+        }
+        LoxError::ParseErrors(errs) => {
+            for err in errs {
+                lines.push(parse_error(err));
+            }
+        }
+        LoxError::ResolverErrors(errs) => {
+            for err in errs {
+                lines.push(resolver_error(err));
+            }
+        }
+        // jlox has no annotations to check
+        LoxError::TypeErrors(errs) => {
+            for err in errs {
+                lines.push(err.to_string());
+            }
+        }
+        LoxError::FormatError(err) => lines.push(err.to_string()),
+        LoxError::RuntimeError(err) => {
+            lines.push(runtime_error(err));
+            lines.push(format!("[line {}]", err.line()));
+        }
+    }
+    lines.join("\n")
+}
+
+fn at(token: &Token, msg: &str) -> String {
+    if token.token == TokenType::Eof {
+        format!("[line {}] Error at end: {msg}", token.line)
+    } else {
+        format!("[line {}] Error at '{}': {msg}", token.line, token.lexeme)
+    }
+}
+
+fn parse_error(err: &ParseError) -> String {
+    match err {
+        ParseError::Expected {
+            expected,
+            found,
+            context,
+        } => {
+            let msg = match *context {
+                "after parameter" => "Expect ')' after parameters.".to_owned(),
+                "after argument" => "Expect ')' after arguments.".to_owned(),
+                _ => match context.strip_prefix("for ") {
+                    Some(what) => format!("Expect {what}."),
+                    None => {
+                        let mut msg = String::from("Expect ");
+                        write!(&mut msg, "{} {context}.", expected[0])
+                            .expect("must be able to append to string");
+                        msg
+                    }
+                },
+            };
+            at(found, &msg)
+        }
+        ParseError::ExpectedExpression { found } => at(found, "Expect expression."),
+        ParseError::InvalidAssignmentTarget { equals } => at(equals, "Invalid assignment target."),
+        ParseError::TooMany { found, what } => {
+            at(found, &format!("Can't have more than 255 {what}."))
+        }
+        ParseError::TooManyErrors(_) => err.to_string(),
+    }
+}
+
+fn resolver_error(err: &ResolverError) -> String {
+    match err {
+        ResolverError::AccessBeforeInit(token) => {
+            at(token, "Can't read local variable in its own initializer.")
+        }
+        ResolverError::AlreadyDefined(token) => {
+            at(token, "Already a variable with this name in this scope.")
+        }
+        ResolverError::NoFuncReturn(line, _) => {
+            format!("[line {line}] Error at 'return': Can't return from top-level code.")
+        }
+        ResolverError::InitializerReturn(line, _) => {
+            format!("[line {line}] Error at 'return': Can't return a value from an initializer.")
+        }
+        ResolverError::NoClassThis(token) => at(token, "Can't use 'this' outside of a class."),
+        ResolverError::NoClassSuper(token) => at(token, "Can't use 'super' outside of a class."),
+        ResolverError::NoSubclassSuper(token) => {
+            at(token, "Can't use 'super' in a class with no superclass.")
+        }
+        ResolverError::InheritFromSelf(token) => at(token, "A class can't inherit from itself."),
+        ResolverError::TraitSuper(token) => at(token, "Can't use 'super' in a trait."),
+        ResolverError::TraitClassMethod(token) => at(token, "Traits can't have class methods."),
+        ResolverError::InitNotMethod(token) => {
+            at(token, "An initializer can't be a getter or class method.")
+        }
+        ResolverError::TraitConflict {
+            class,
+            method,
+            first,
+            second,
+        } => at(
+            class,
+            &format!("Method '{method}' comes from both '{first}' and '{second}'."),
+        ),
+    }
+}
+
+fn runtime_error(err: &RuntimeError) -> String {
+    match err {
+        RuntimeError::ArityMismatch { got, want, .. } => {
+            format!("Expected {want} arguments but got {got}.")
+        }
+        RuntimeError::TypeMismatch {
+            operator, operands, ..
+        } => match (operator.as_str(), operands.len()) {
+            (_, 1) => "Operand must be a number.".to_owned(),
+            ("+", _) => "Operands must be two numbers or two strings.".to_owned(),
+            _ => "Operands must be numbers.".to_owned(),
+        },
+        RuntimeError::UndefinedVariable { name, .. } => format!("Undefined variable '{name}'."),
+        RuntimeError::UndefinedProperty { name, .. } => format!("Undefined property '{name}'."),
+        RuntimeError::PropertyOnNonInstance { .. } => "Only instances have properties.".to_owned(),
+        RuntimeError::FieldOnNonInstance { .. } => "Only instances have fields.".to_owned(),
+        RuntimeError::SuperclassNotClass { .. } => "Superclass must be a class.".to_owned(),
+        RuntimeError::NonCallableCalled { .. } => "Can only call functions and classes.".to_owned(),
+        RuntimeError::StackOverflow { .. } => "Stack overflow.".to_owned(),
+        // no jlox equivalent; keep ours without the location
+        _ => {
+            let msg = err.to_string();
+            match msg.split_once("Error: ") {
+                Some((_, msg)) => msg.to_owned(),
+                None => msg,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::LoxResult;
+    use crate::interpreter::Interpreter;
+    use crate::output::SharedBuffer;
+    use crate::parser::Parser;
+    use crate::resolver::Resolver;
+    use crate::scanner::Scanner;
+
+    fn run(input: &str) -> LoxResult<()> {
+        let mut scanner = Scanner::new(input);
+        let tokens = scanner.scan_tokens()?;
+        let mut parser = Parser::new(&tokens);
+        let stmts = parser.parse()?;
+        let mut resolver = Resolver::default();
+        let mut interpreter = Interpreter::builder()
+            .allow_stdout()
+            .stdout(SharedBuffer::default())
+            .build();
+        interpreter.resolutions = resolver.resolve(&stmts)?;
+        Ok(interpreter.interpret(&stmts)?)
+    }
+
+    #[rstest::rstest]
+    #[case("print \"a;", "[line 1] Error: Unterminated string.", 65)]
+    #[case("var x = 1;\n@", "[line 2] Error: Unexpected character.", 65)]
+    #[case("print 1", "[line 1] Error at end: Expect ';' after value.", 65)]
+    #[case("var 4;", "[line 1] Error at '4': Expect variable name.", 65)]
+    #[case("f(1, 2;", "[line 1] Error at ';': Expect ')' after arguments.", 65)]
+    #[case(
+        "if (true print 1;",
+        "[line 1] Error at 'print': Expect ')' after if condition.",
+        65
+    )]
+    #[case("a + b = c;", "[line 1] Error at '=': Invalid assignment target.", 65)]
+    #[case("x.;", "[line 1] Error at ';': Expect property name after '.'.", 65)]
+    #[case(
+        "fun f() {\n  var a;\n  var a;\n}",
+        "[line 3] Error at 'a': Already a variable with this name in this scope.",
+        65
+    )]
+    #[case(
+        "return 1;",
+        "[line 1] Error at 'return': Can't return from top-level code.",
+        65
+    )]
+    #[case("print nope;", "Undefined variable 'nope'.\n[line 1]", 70)]
+    #[case("fun f(a) {}\n\nf();", "Expected 1 arguments but got 0.\n[line 3]", 70)]
+    #[case("\"not\"();", "Can only call functions and classes.\n[line 1]", 70)]
+    #[case("1 -\n\"x\";", "Operands must be numbers.\n[line 1]", 70)]
+    #[case("-\"x\";", "Operand must be a number.\n[line 1]", 70)]
+    #[case(
+        "true + nil;",
+        "Operands must be two numbers or two strings.\n[line 1]",
+        70
+    )]
+    #[case("1 < \"1\";", "Operands must be numbers.\n[line 1]", 70)]
+    #[case("class A {}\nA().b;", "Undefined property 'b'.\n[line 2]", 70)]
+    #[case("true.x;", "Only instances have properties.\n[line 1]", 70)]
+    #[case("nil.x = 1;", "Only instances have fields.\n[line 1]", 70)]
+    #[case(
+        "var A = 1;\nclass B < A {}",
+        "Superclass must be a class.\n[line 2]",
+        70
+    )]
+    #[case("var a = 1;\n{\n  b = a;\n}", "Undefined variable 'b'.\n[line 3]", 70)]
+    fn test_report(#[case] input: &str, #[case] want: &str, #[case] exit_code: i32) {
+        let err = run(input).expect_err("should fail");
+        assert_eq!(report(&err), want);
+        assert_eq!(err.exit_code(), exit_code);
+    }
+}
@@ -0,0 +1,30 @@
+#![feature(hash_raw_entry)]
+#![feature(let_chains)]
+
+pub mod callable;
+pub mod capabilities;
+pub mod checker;
+pub mod class;
+pub mod debugger;
+pub mod environment;
+pub mod error;
+pub mod expr;
+pub mod expr_eval;
+pub mod formatter;
+pub mod fuzz;
+pub mod interpreter;
+pub mod jlox;
+pub mod limits;
+pub mod lsp;
+pub mod models;
+pub mod natives;
+pub mod output;
+pub mod parser;
+pub mod profiler;
+pub mod resolver;
+pub mod scanner;
+pub mod stmt;
+pub mod stmt_eval;
+pub mod symbols;
+pub mod token;
+pub mod value;
@@ -0,0 +1,268 @@
+use crate::error::RuntimeError;
+use crate::interpreter::Interpreter;
+use compact_str::CompactString;
+use std::time::Duration;
+use std::time::Instant;
+
+// Every Lox call recurses on the native stack (several KB a call, far more in
+// debug builds), so hosts need room for this many nested calls.
+pub const DEFAULT_MAX_DEPTH: usize = 1000;
+
+// Calls stop this close to the end of the native stack, whatever the depth
+// limit, leaving room for the call in progress to unwind with an error.
+const STACK_RED_ZONE: usize = 256 << 10;
+
+// the clock is only read this often, in steps
+const TIMEOUT_CHECK_INTERVAL: u64 = 256;
+
+// Caps on what a script may use, None meaning unlimited. Steps and time count
+// from the start of each run, while objects and string bytes count everything
+// allocated over the interpreter's life, not what is live at any point.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Limits {
+    // statements executed, counting each loop iteration as one more
+    pub max_steps: Option<u64>,
+    // nested calls
+    pub max_depth: Option<usize>,
+    // instances, functions and classes created
+    pub max_objects: Option<usize>,
+    // bytes of strings built at runtime
+    pub max_string_bytes: Option<usize>,
+    // measured from the first statement of the run
+    pub timeout: Option<Duration>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_steps: None,
+            max_depth: Some(DEFAULT_MAX_DEPTH),
+            max_objects: None,
+            max_string_bytes: None,
+            timeout: None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Usage {
+    pub steps: u64,
+    pub objects: usize,
+    pub string_bytes: usize,
+    started: Option<Instant>,
+}
+
+impl Usage {
+    pub(crate) fn start_run(&mut self) {
+        self.steps = 0;
+        self.started = None;
+    }
+}
+
+impl Interpreter {
+    pub(crate) fn current_line(&self) -> CompactString {
+        match self.frames.last() {
+            None => "TODO".into(),
+            Some(frame) => format!("{}", frame.line).into(),
+        }
+    }
+
+    pub(crate) fn tick(&mut self) -> Result<(), RuntimeError> {
+        let usage = &mut self.usage;
+        usage.steps += 1;
+        if let Some(limit) = self.limits.max_steps
+            && usage.steps > limit
+        {
+            return Err(RuntimeError::StepLimit {
+                line: self.current_line(),
+                limit,
+            });
+        }
+        if let Some(limit) = self.limits.timeout {
+            let started = *usage.started.get_or_insert_with(Instant::now);
+            if usage.steps.is_multiple_of(TIMEOUT_CHECK_INTERVAL) && started.elapsed() > limit {
+                return Err(RuntimeError::Timeout {
+                    line: self.current_line(),
+                    limit,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_depth(&self) -> Result<(), RuntimeError> {
+        // the script's own frame doesn't count
+        let depth = self.frames.len();
+        let limit = match self.limits.max_depth {
+            Some(limit) if depth > limit => limit,
+            // a host thread with a small stack runs out before the limit
+            _ if stacker::remaining_stack().is_some_and(|left| left < STACK_RED_ZONE) => {
+                depth.saturating_sub(1)
+            }
+            _ => return Ok(()),
+        };
+        Err(RuntimeError::StackOverflow {
+            line: self.current_line(),
+            limit,
+        })
+    }
+
+    pub(crate) fn track_object(&mut self) -> Result<(), RuntimeError> {
+        self.usage.objects += 1;
+        match self.limits.max_objects {
+            Some(limit) if self.usage.objects > limit => Err(RuntimeError::ObjectLimit {
+                line: self.current_line(),
+                limit,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn track_string(&mut self, bytes: usize) -> Result<(), RuntimeError> {
+        self.usage.string_bytes += bytes;
+        match self.limits.max_string_bytes {
+            Some(limit) if self.usage.string_bytes > limit => Err(RuntimeError::StringLimit {
+                line: self.current_line(),
+                limit,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::LoxError;
+    use crate::error::LoxResult;
+    use crate::parser::Parser;
+    use crate::resolver::Resolver;
+    use crate::scanner::Scanner;
+    use std::thread;
+
+    fn limited_eval(input: &str, limits: Limits) -> LoxResult<()> {
+        let mut interpreter = Interpreter {
+            limits,
+            ..Default::default()
+        };
+        let mut scanner = Scanner::new(input);
+        let tokens = scanner.scan_tokens()?;
+        let mut parser = Parser::new(&tokens);
+        let stmts = parser.parse()?;
+        let mut resolver = Resolver::default();
+        interpreter.resolutions = resolver.resolve(&stmts)?;
+        Ok(interpreter.interpret(&stmts)?)
+    }
+
+    #[rstest::rstest]
+    #[case(
+        "var i = 0;\nwhile (true) {}",
+        Limits { max_steps: Some(1000), ..Default::default() },
+        "[line 2] Error: step limit of 1000 exceeded"
+    )]
+    #[case(
+        "fun f(n) {\n  return f(n + 1);\n}\nf(0);",
+        Limits { max_depth: Some(20), ..Default::default() },
+        "[line 2] Error: stack overflow: more than 20 nested calls"
+    )]
+    #[case(
+        "class A {}\nwhile (true) A();",
+        Limits { max_objects: Some(100), ..Default::default() },
+        "[line 2] Error: more than 100 objects allocated"
+    )]
+    #[case(
+        "var s = \"x\";\nwhile (true) s = s + s;",
+        Limits { max_string_bytes: Some(1 << 20), ..Default::default() },
+        "[line 2] Error: more than 1048576 bytes of strings allocated"
+    )]
+    #[case(
+        "while (true) {}",
+        Limits { timeout: Some(Duration::from_millis(10)), ..Default::default() },
+        "[line 1] Error: timed out after 10ms"
+    )]
+    fn test_limits(
+        #[case] input: &str,
+        #[case] limits: Limits,
+        #[case] want: &str,
+    ) -> LoxResult<()> {
+        let got = limited_eval(input, limits).expect_err("should hit the limit");
+        assert_eq!(format!("{got}"), want);
+        Ok(())
+    }
+
+    #[test]
+    fn test_within_limits() -> LoxResult<()> {
+        let limits = Limits {
+            max_steps: Some(100),
+            max_depth: Some(3),
+            max_objects: Some(3),
+            max_string_bytes: Some(6),
+            timeout: Some(Duration::from_secs(60)),
+        };
+        let input =
+            "fun f(n) { if (n > 0) f(n - 1); }\nf(2);\nclass A {}\nA();\nprint \"ab\" + \"cd\";";
+        limited_eval(input, limits)
+    }
+
+    #[test]
+    fn test_limits_per_run() -> LoxResult<()> {
+        let mut interpreter = Interpreter {
+            limits: Limits {
+                max_steps: Some(1000),
+                timeout: Some(Duration::from_millis(10)),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut scanner = Scanner::new("for (var i = 0; i < 300; i = i + 1) {}");
+        let tokens = scanner.scan_tokens()?;
+        let stmts = Parser::new(&tokens).parse()?;
+        interpreter.resolutions = Resolver::default().resolve(&stmts)?;
+        // each run alone is within both limits, but together they're past them
+        for _ in 0..3 {
+            interpreter.interpret(&stmts)?;
+            thread::sleep(Duration::from_millis(10));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_default_depth() {
+        // the default limit needs more stack than a test thread gets
+        let input = "fun f(n) {\n  return f(n + 1);\n}\nf(0);";
+        let overflowed = thread::Builder::new()
+            .stack_size(64 << 20)
+            .spawn(move || {
+                matches!(
+                    limited_eval(input, Limits::default()),
+                    Err(LoxError::RuntimeError(RuntimeError::StackOverflow {
+                        limit: DEFAULT_MAX_DEPTH,
+                        ..
+                    }))
+                )
+            })
+            .expect("must be able to spawn")
+            .join()
+            .expect("should not overflow the native stack");
+        assert!(overflowed);
+    }
+
+    #[test]
+    fn test_small_stack() {
+        // an embedder's thread may not fit the default limit
+        let input = "fun f(n) {\n  return f(n + 1);\n}\nf(0);";
+        let overflowed = thread::Builder::new()
+            .stack_size(2 << 20)
+            .spawn(move || {
+                matches!(
+                    limited_eval(input, Limits::default()),
+                    Err(LoxError::RuntimeError(RuntimeError::StackOverflow { limit, .. }))
+                        if limit < DEFAULT_MAX_DEPTH
+                )
+            })
+            .expect("must be able to spawn")
+            .join()
+            .expect("should not overflow the native stack");
+        assert!(overflowed);
+    }
+}
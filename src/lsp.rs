@@ -0,0 +1,891 @@
+use crate::checker::Checker;
+use crate::checker::TypeError;
+use crate::error::ParseError;
+use crate::formatter::fun_span;
+use crate::formatter::stmt_span;
+use crate::formatter::Span;
+use crate::models::FunDecl;
+use crate::models::Stmt;
+use crate::models::StmtList;
+use crate::models::Token;
+use crate::natives::NATIVES;
+use crate::parser::Parser;
+use crate::resolver::Resolver;
+use crate::resolver::ResolverError;
+use crate::scanner::Scanner;
+use crate::symbols::DeclKind;
+use crate::symbols::Symbols;
+use serde_json::json;
+use serde_json::Value as Json;
+use std::collections::HashMap;
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+
+const METHOD_NOT_FOUND: i64 = -32601;
+
+// LSP SymbolKind and CompletionItemKind values
+const SYMBOL_CLASS: u64 = 5;
+const SYMBOL_METHOD: u64 = 6;
+const SYMBOL_INTERFACE: u64 = 11;
+const SYMBOL_FUNCTION: u64 = 12;
+const COMPLETION_FUNCTION: u64 = 3;
+const COMPLETION_VARIABLE: u64 = 6;
+const COMPLETION_CLASS: u64 = 7;
+const COMPLETION_INTERFACE: u64 = 8;
+
+pub fn read_message(input: &mut impl BufRead) -> io::Result<Option<Json>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let length = length
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length"))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+pub fn write_message(output: &mut impl Write, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    output.flush()
+}
+
+// How the client counts the characters of a line; UTF-16 unless it offers
+// something else.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+enum Encoding {
+    Utf8,
+    #[default]
+    Utf16,
+    Utf32,
+}
+
+impl Encoding {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "utf-8" => Some(Self::Utf8),
+            "utf-16" => Some(Self::Utf16),
+            "utf-32" => Some(Self::Utf32),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Utf8 => "utf-8",
+            Self::Utf16 => "utf-16",
+            Self::Utf32 => "utf-32",
+        }
+    }
+
+    fn units(self, c: char) -> usize {
+        match self {
+            Self::Utf8 => c.len_utf8(),
+            Self::Utf16 => c.len_utf16(),
+            Self::Utf32 => 1,
+        }
+    }
+}
+
+// A document's lines, to turn token columns, which count chars, into the
+// client's characters and back.
+struct Lines {
+    lines: Vec<String>,
+    encoding: Encoding,
+}
+
+impl Lines {
+    fn new(src: &str, encoding: Encoding) -> Self {
+        Self {
+            lines: src.split('\n').map(String::from).collect(),
+            encoding,
+        }
+    }
+
+    // The client's 0-based character for a 1-based column.
+    fn character(&self, line: usize, column: usize) -> usize {
+        let text = self.lines.get(line - 1).map_or("", String::as_str);
+        let mut chars = text.chars();
+        (1..column)
+            .map(|_| chars.next().map_or(1, |c| self.encoding.units(c)))
+            .sum()
+    }
+
+    // The 1-based column holding the client's 0-based character.
+    fn column(&self, line: usize, character: usize) -> usize {
+        let text = self.lines.get(line - 1).map_or("", String::as_str);
+        let mut column = 1;
+        let mut units = 0;
+        for c in text.chars() {
+            units += self.encoding.units(c);
+            if units > character {
+                return column;
+            }
+            column += 1;
+        }
+        column + character - units
+    }
+
+    fn token_range(&self, token: &Token) -> Json {
+        let start = self.character(token.line, token.column);
+        // strings may run over several lines
+        let (end_line, end) = match token.lexeme.rsplit_once('\n') {
+            None => (token.line, token.column + token.lexeme.chars().count()),
+            Some((before, last)) => (
+                token.line + before.matches('\n').count() + 1,
+                last.chars().count() + 1,
+            ),
+        };
+        json!({
+            "start": {"line": token.line - 1, "character": start},
+            "end": {"line": end_line - 1, "character": self.character(end_line, end)},
+        })
+    }
+
+    fn location(&self, uri: &str, token: &Token) -> Json {
+        json!({"uri": uri, "range": self.token_range(token)})
+    }
+}
+
+// Everything we know about one open file, recomputed on every change.
+struct Document {
+    stmts: StmtList,
+    symbols: Symbols,
+    lines: Lines,
+    diagnostics: Vec<Json>,
+}
+
+impl Document {
+    fn analyze(src: &str, encoding: Encoding) -> Self {
+        let lines = Lines::new(src, encoding);
+        let token_range = |token: &Token| lines.token_range(token);
+        let mut scanner = Scanner::new(src);
+        let (tokens, scan_errors) = scanner.scan_partial();
+        let mut parser = Parser::new(&tokens);
+        let (stmts, parse_errors) = parser.parse_partial();
+        let mut resolver = Resolver::default();
+        let resolver_errors = resolver.resolve(&stmts).err().unwrap_or_default();
+        let type_errors = Checker::default().check(&stmts).err().unwrap_or_default();
+
+        let mut diagnostics = vec![];
+        for err in scan_errors {
+            diagnostics.push(diagnostic(line_range(err.line), err.to_string()));
+        }
+        for err in parse_errors {
+            let range = match &err {
+                ParseError::Expected { found, .. }
+                | ParseError::ExpectedExpression { found }
+                | ParseError::TooMany { found, .. } => token_range(found),
+                ParseError::InvalidAssignmentTarget { equals } => token_range(equals),
+                ParseError::TooManyErrors(line) => line_range(*line),
+            };
+            diagnostics.push(diagnostic(range, err.to_string()));
+        }
+        for err in resolver_errors {
+            let range = match &err {
+                ResolverError::AccessBeforeInit(token)
+                | ResolverError::AlreadyDefined(token)
+                | ResolverError::NoClassThis(token)
+                | ResolverError::NoClassSuper(token)
+                | ResolverError::NoSubclassSuper(token)
+                | ResolverError::InheritFromSelf(token)
+                | ResolverError::TraitSuper(token)
+                | ResolverError::TraitClassMethod(token)
+                | ResolverError::InitNotMethod(token)
+                | ResolverError::TraitConflict { class: token, .. } => token_range(token),
+                ResolverError::NoFuncReturn(line, _)
+                | ResolverError::InitializerReturn(line, _) => line_range(*line),
+            };
+            diagnostics.push(diagnostic(range, err.to_string()));
+        }
+        for err in type_errors {
+            let range = match &err {
+                TypeError::UnknownType(token) => token_range(token),
+                TypeError::Mismatch { line, .. }
+                | TypeError::Arity { line, .. }
+                | TypeError::Operands { line, .. }
+                | TypeError::NotInstance { line, .. } => line_range(*line),
+            };
+            diagnostics.push(diagnostic(range, err.to_string()));
+        }
+        Self {
+            stmts,
+            symbols: resolver.into_symbols(),
+            lines,
+            diagnostics,
+        }
+    }
+
+    // LSP positions are 0-based, tokens are 1-based.
+    fn position(&self, params: &Json) -> Option<(usize, usize)> {
+        let line = params["position"]["line"].as_u64()? as usize + 1;
+        let character = params["position"]["character"].as_u64()? as usize;
+        Some((line, self.lines.column(line, character)))
+    }
+}
+
+#[derive(Default)]
+pub struct Server {
+    documents: HashMap<String, Document>,
+    encoding: Encoding,
+    shutdown: bool,
+    // set once the client asks us to exit
+    pub exit_code: Option<i32>,
+}
+
+impl Server {
+    // Handles one message from the client and returns what to send back.
+    pub fn handle(&mut self, message: &Json) -> Vec<Json> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .unwrap_or_default()
+            .to_owned();
+        let Some(id) = message.get("id").cloned() else {
+            return self.notification(method, params, uri);
+        };
+        let result = match method {
+            "initialize" => {
+                self.encoding = negotiate_encoding(params);
+                capabilities(self.encoding)
+            }
+            "shutdown" => {
+                self.shutdown = true;
+                Json::Null
+            }
+            "textDocument/definition" => self.definition(params, &uri),
+            "textDocument/references" => self.references(params, &uri),
+            "textDocument/hover" => self.hover(params, &uri),
+            "textDocument/documentSymbol" => self.document_symbols(&uri),
+            "textDocument/completion" => self.completion(params, &uri),
+            _ => {
+                return vec![json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": {
+                        "code": METHOD_NOT_FOUND,
+                        "message": format!("unsupported method: {method}"),
+                    },
+                })]
+            }
+        };
+        vec![json!({"jsonrpc": "2.0", "id": id, "result": result})]
+    }
+
+    fn notification(&mut self, method: &str, params: &Json, uri: String) -> Vec<Json> {
+        let text = match method {
+            "exit" => {
+                self.exit_code = Some(if self.shutdown { 0 } else { 1 });
+                return vec![];
+            }
+            "textDocument/didOpen" => params["textDocument"]["text"].as_str(),
+            "textDocument/didChange" => params["contentChanges"]
+                .as_array()
+                .and_then(|changes| changes.last())
+                .and_then(|change| change["text"].as_str()),
+            "textDocument/didSave" => params["text"].as_str(),
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                return vec![publish_diagnostics(&uri, vec![])];
+            }
+            _ => return vec![],
+        };
+        if let Some(text) = text {
+            self.documents
+                .insert(uri.clone(), Document::analyze(text, self.encoding));
+        }
+        match self.documents.get(&uri) {
+            None => vec![],
+            Some(doc) => vec![publish_diagnostics(&uri, doc.diagnostics.clone())],
+        }
+    }
+
+    // The document and the declaration under the cursor in a request.
+    fn lookup(&self, params: &Json, uri: &str) -> Option<(&Document, usize)> {
+        let doc = self.documents.get(uri)?;
+        let (line, column) = doc.position(params)?;
+        let decl = doc.symbols.declaration_at(line, column)?;
+        Some((doc, decl))
+    }
+
+    fn definition(&self, params: &Json, uri: &str) -> Json {
+        match self.lookup(params, uri) {
+            None => Json::Null,
+            Some((doc, decl)) => doc
+                .lines
+                .location(uri, &doc.symbols.declarations[decl].name),
+        }
+    }
+
+    fn references(&self, params: &Json, uri: &str) -> Json {
+        let Some((doc, decl)) = self.lookup(params, uri) else {
+            return json!([]);
+        };
+        let mut locations = vec![];
+        if params["context"]["includeDeclaration"].as_bool() == Some(true) {
+            locations.push(
+                doc.lines
+                    .location(uri, &doc.symbols.declarations[decl].name),
+            );
+        }
+        for token in doc.symbols.references_to(decl) {
+            locations.push(doc.lines.location(uri, token));
+        }
+        Json::Array(locations)
+    }
+
+    fn hover(&self, params: &Json, uri: &str) -> Json {
+        let Some((doc, decl)) = self.lookup(params, uri) else {
+            return Json::Null;
+        };
+        let decl = &doc.symbols.declarations[decl];
+        let fun_decl = find_function(&doc.stmts, &decl.name);
+        let mut value = match fun_decl {
+            Some(fun_decl) if fun_decl.is_static => {
+                format!("static {} {}", decl.kind, decl.name.lexeme)
+            }
+            _ => format!("{} {}", decl.kind, decl.name.lexeme),
+        };
+        match fun_decl {
+            // getters read like fields
+            Some(fun_decl) if fun_decl.is_getter => annotate(&mut value, &fun_decl.return_type),
+            Some(fun_decl) => {
+                value.push('(');
+                for (i, parameter) in fun_decl.parameters.iter().enumerate() {
+                    if i > 0 {
+                        value.push_str(", ");
+                    }
+                    value.push_str(&parameter.lexeme);
+                    annotate(&mut value, &fun_decl.parameter_types[i]);
+                }
+                value.push(')');
+                annotate(&mut value, &fun_decl.return_type);
+            }
+            None => {}
+        }
+        json!({
+            "contents": {"kind": "plaintext", "value": value},
+            "range": doc.lines.token_range(&decl.name),
+        })
+    }
+
+    fn document_symbols(&self, uri: &str) -> Json {
+        match self.documents.get(uri) {
+            None => json!([]),
+            Some(doc) => Json::Array(document_symbols(&doc.lines, &doc.stmts)),
+        }
+    }
+
+    fn completion(&self, params: &Json, uri: &str) -> Json {
+        let Some(doc) = self.documents.get(uri) else {
+            return json!([]);
+        };
+        let Some((line, _)) = doc.position(params) else {
+            return json!([]);
+        };
+        let mut items = vec![];
+        for decl in doc.symbols.visible_at(line) {
+            let kind = match decl.kind {
+                // methods are only reachable through an instance or a class
+                DeclKind::Method => continue,
+                DeclKind::Function => COMPLETION_FUNCTION,
+                DeclKind::Class => COMPLETION_CLASS,
+                DeclKind::Trait => COMPLETION_INTERFACE,
+                DeclKind::Variable | DeclKind::Parameter => COMPLETION_VARIABLE,
+            };
+            items.push(json!({
+                "label": decl.name.lexeme.as_str(),
+                "kind": kind,
+                "detail": decl.kind.to_string(),
+            }));
+        }
+        // natives are defined by the interpreter, so the resolver never sees them
+        for native in NATIVES {
+            items.push(json!({
+                "label": native.name,
+                "kind": COMPLETION_FUNCTION,
+                "detail": "native function",
+            }));
+        }
+        Json::Array(items)
+    }
+}
+
+// The first encoding the client offers that we know, in its order of
+// preference.
+fn negotiate_encoding(params: &Json) -> Encoding {
+    params["capabilities"]["general"]["positionEncodings"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find_map(|name| Encoding::from_name(name.as_str()?))
+        .unwrap_or_default()
+}
+
+fn capabilities(encoding: Encoding) -> Json {
+    json!({
+        "capabilities": {
+            "positionEncoding": encoding.name(),
+            "textDocumentSync": {"openClose": true, "change": 1, "save": {"includeText": true}},
+            "definitionProvider": true,
+            "referencesProvider": true,
+            "hoverProvider": true,
+            "documentSymbolProvider": true,
+            "completionProvider": {},
+        },
+        "serverInfo": {"name": "lox-lsp"},
+    })
+}
+
+fn line_range(line: usize) -> Json {
+    lines_range(Some((line, line)))
+}
+
+// Whole lines, ending at the start of the line after the last one.
+fn lines_range(span: Span) -> Json {
+    let (first, last) = span.unwrap_or((1, 1));
+    json!({
+        "start": {"line": first - 1, "character": 0},
+        "end": {"line": last, "character": 0},
+    })
+}
+
+fn diagnostic(range: Json, message: String) -> Json {
+    json!({"range": range, "severity": 1, "source": "lox", "message": message})
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Json>) -> Json {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": {"uri": uri, "diagnostics": diagnostics},
+    })
+}
+
+fn document_symbol(
+    lines: &Lines,
+    name: &Token,
+    kind: u64,
+    range: Json,
+    children: Vec<Json>,
+) -> Json {
+    json!({
+        "name": name.lexeme.as_str(),
+        "kind": kind,
+        "range": range,
+        "selectionRange": lines.token_range(name),
+        "children": children,
+    })
+}
+
+fn document_symbols(lines: &Lines, stmts: &StmtList) -> Vec<Json> {
+    let mut symbols = vec![];
+    for stmt in stmts {
+        match stmt {
+            Stmt::FunDecl(fun_decl) => symbols.push(document_symbol(
+                lines,
+                &fun_decl.name,
+                SYMBOL_FUNCTION,
+                lines_range(fun_span(fun_decl)),
+                document_symbols(lines, &fun_decl.body),
+            )),
+            Stmt::ClassDecl { name, methods, .. } | Stmt::TraitDecl { name, methods, .. } => {
+                let kind = match stmt {
+                    Stmt::TraitDecl { .. } => SYMBOL_INTERFACE,
+                    _ => SYMBOL_CLASS,
+                };
+                let methods = methods
+                    .iter()
+                    .map(|method| {
+                        document_symbol(
+                            lines,
+                            &method.name,
+                            SYMBOL_METHOD,
+                            lines_range(fun_span(method)),
+                            document_symbols(lines, &method.body),
+                        )
+                    })
+                    .collect();
+                symbols.push(document_symbol(
+                    lines,
+                    name,
+                    kind,
+                    lines_range(stmt_span(stmt)),
+                    methods,
+                ));
+            }
+            Stmt::Block(stmts) => symbols.extend(document_symbols(lines, stmts)),
+            Stmt::IfThenElse {
+                then_stmt,
+                else_stmt,
+                ..
+            } => {
+                symbols.extend(nested_symbols(lines, then_stmt));
+                if let Some(else_stmt) = else_stmt {
+                    symbols.extend(nested_symbols(lines, else_stmt));
+                }
+            }
+            Stmt::While(_, _, body) => symbols.extend(nested_symbols(lines, body)),
+            _ => {}
+        }
+    }
+    symbols
+}
+
+fn nested_symbols(lines: &Lines, stmt: &Stmt) -> Vec<Json> {
+    match stmt {
+        Stmt::Block(stmts) => document_symbols(lines, stmts),
+        _ => vec![],
+    }
+}
+
+fn annotate(value: &mut String, annotation: &Option<Token>) {
+    if let Some(annotation) = annotation {
+        value.push_str(": ");
+        value.push_str(&annotation.lexeme);
+    }
+}
+
+fn find_function<'a>(stmts: &'a StmtList, name: &Token) -> Option<&'a FunDecl> {
+    stmts
+        .into_iter()
+        .find_map(|stmt| find_function_in(stmt, name))
+}
+
+fn find_function_in<'a>(stmt: &'a Stmt, name: &Token) -> Option<&'a FunDecl> {
+    match stmt {
+        Stmt::FunDecl(fun_decl) => {
+            if fun_decl.name == *name {
+                Some(fun_decl)
+            } else {
+                find_function(&fun_decl.body, name)
+            }
+        }
+        Stmt::ClassDecl { methods, .. } | Stmt::TraitDecl { methods, .. } => {
+            methods.iter().find_map(|method| {
+                if method.name == *name {
+                    Some(method)
+                } else {
+                    find_function(&method.body, name)
+                }
+            })
+        }
+        Stmt::Block(stmts) => find_function(stmts, name),
+        Stmt::IfThenElse {
+            then_stmt,
+            else_stmt,
+            ..
+        } => find_function_in(then_stmt, name)
+            .or_else(|| else_stmt.as_ref().and_then(|s| find_function_in(s, name))),
+        Stmt::While(_, _, body) => find_function_in(body, name),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URI: &str = "file:///test.lox";
+
+    const SCRIPT: &str = r#"var total = 0;
+fun add(a, b) {
+  var sum = a + b;
+  return sum;
+}
+class Point {
+  init(x: num) {
+    this.x = x;
+  }
+}
+total = add(total, 1);
+print undefined;
+"#;
+
+    fn open(server: &mut Server, text: &str) -> Vec<Json> {
+        server.handle(&json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": {"textDocument": {"uri": URI, "languageId": "lox", "version": 1, "text": text}},
+        }))
+    }
+
+    fn request(server: &mut Server, method: &str, line: u64, character: u64) -> Json {
+        let mut replies = server.handle(&json!({
+            "jsonrpc": "2.0",
+            "id": 7,
+            "method": method,
+            "params": {
+                "textDocument": {"uri": URI},
+                "position": {"line": line, "character": character},
+                "context": {"includeDeclaration": true},
+            },
+        }));
+        assert_eq!(replies.len(), 1);
+        replies.remove(0)["result"].take()
+    }
+
+    fn range(line: u64, start: u64, end: u64) -> Json {
+        json!({
+            "start": {"line": line, "character": start},
+            "end": {"line": line, "character": end},
+        })
+    }
+
+    #[test]
+    fn test_framing() -> io::Result<()> {
+        let message = json!({"jsonrpc": "2.0", "id": 1, "method": "shutdown"});
+        let mut buf = vec![];
+        write_message(&mut buf, &message)?;
+        write_message(&mut buf, &message)?;
+        let mut input = &buf[..];
+        assert_eq!(read_message(&mut input)?, Some(message.clone()));
+        assert_eq!(read_message(&mut input)?, Some(message));
+        assert_eq!(read_message(&mut input)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_lifecycle() {
+        let mut server = Server::default();
+        let replies = server
+            .handle(&json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}));
+        assert_eq!(replies[0]["result"]["capabilities"]["hoverProvider"], true);
+        assert_eq!(
+            replies[0]["result"]["capabilities"]["positionEncoding"],
+            "utf-16"
+        );
+        let replies = server.handle(&json!({"jsonrpc": "2.0", "id": 2, "method": "bogus"}));
+        assert_eq!(replies[0]["error"]["code"], METHOD_NOT_FOUND);
+        server.handle(&json!({"jsonrpc": "2.0", "id": 3, "method": "shutdown"}));
+        assert_eq!(server.exit_code, None);
+        server.handle(&json!({"jsonrpc": "2.0", "method": "exit"}));
+        assert_eq!(server.exit_code, Some(0));
+    }
+
+    #[rstest::rstest]
+    #[case(json!({}), "utf-16", 20)]
+    #[case(json!(["utf-16"]), "utf-16", 20)]
+    #[case(json!(["utf-32", "utf-16"]), "utf-32", 19)]
+    #[case(json!(["utf-7", "utf-8"]), "utf-8", 22)]
+    fn test_position_encoding(#[case] offered: Json, #[case] want: &str, #[case] character: u64) {
+        let mut server = Server::default();
+        let replies = server.handle(&json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": {"capabilities": {"general": {"positionEncodings": offered}}},
+        }));
+        assert_eq!(
+            replies[0]["result"]["capabilities"]["positionEncoding"],
+            want
+        );
+        // the emoji is two UTF-16 code units and four UTF-8 bytes
+        open(&mut server, "var s = \"😀\"; print s;");
+        let got = request(&mut server, "textDocument/definition", 0, character);
+        assert_eq!(got, json!({"uri": URI, "range": range(0, 4, 5)}));
+        let got = request(&mut server, "textDocument/references", 0, 4);
+        assert_eq!(got[1]["range"], range(0, character, character + 1));
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let mut server = Server::default();
+        let replies = open(
+            &mut server,
+            "var a = 1;\nprint a +;\n{ var b = b; }\nvar s = \"open",
+        );
+        assert_eq!(replies[0]["method"], "textDocument/publishDiagnostics");
+        let diagnostics = replies[0]["params"]["diagnostics"].as_array().unwrap();
+        let got: Vec<_> = diagnostics
+            .iter()
+            .map(|d| {
+                (
+                    d["range"]["start"]["line"].as_u64().unwrap(),
+                    d["message"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            got,
+            vec![
+                (3, "[line 4] Error: Unterminated string."),
+                (1, "[line 2] Error at ';': expected expression"),
+                (3, "[line 4] Error at end: expected expression"),
+                (
+                    2,
+                    "[line 3] Error at 'b': variable accessed before definition"
+                ),
+            ]
+        );
+        assert_eq!(diagnostics[1]["range"], range(1, 9, 10));
+
+        let replies = server.handle(&json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didChange",
+            "params": {"textDocument": {"uri": URI, "version": 2}, "contentChanges": [{"text": "print 1;"}]},
+        }));
+        assert_eq!(replies[0]["params"]["diagnostics"], json!([]));
+    }
+
+    #[test]
+    fn test_multiline_token_range() {
+        let mut server = Server::default();
+        let replies = open(&mut server, "var a = 1 \"x\n  y\";");
+        let diagnostics = &replies[0]["params"]["diagnostics"];
+        assert_eq!(
+            diagnostics[0]["range"],
+            json!({
+                "start": {"line": 0, "character": 10},
+                "end": {"line": 1, "character": 4},
+            })
+        );
+    }
+
+    #[test]
+    fn test_navigation() {
+        let mut server = Server::default();
+        open(&mut server, SCRIPT);
+
+        // `sum` in `return sum;`
+        let got = request(&mut server, "textDocument/definition", 3, 10);
+        assert_eq!(got, json!({"uri": URI, "range": range(2, 6, 9)}));
+
+        // `total` in its declaration
+        let got = request(&mut server, "textDocument/references", 0, 5);
+        assert_eq!(
+            got,
+            json!([
+                {"uri": URI, "range": range(0, 4, 9)},
+                {"uri": URI, "range": range(10, 12, 17)},
+                {"uri": URI, "range": range(10, 0, 5)},
+            ])
+        );
+
+        // an undefined global has nowhere to go
+        assert_eq!(
+            request(&mut server, "textDocument/definition", 11, 7),
+            Json::Null
+        );
+    }
+
+    #[test]
+    fn test_hover() {
+        let mut server = Server::default();
+        open(&mut server, SCRIPT);
+        let got = request(&mut server, "textDocument/hover", 10, 9);
+        assert_eq!(got["contents"]["value"], "function add(a, b)");
+        let got = request(&mut server, "textDocument/hover", 2, 12);
+        assert_eq!(got["contents"]["value"], "parameter a");
+        let got = request(&mut server, "textDocument/hover", 6, 3);
+        assert_eq!(got["contents"]["value"], "method init(x: num)");
+    }
+
+    #[test]
+    fn test_hover_class_methods() {
+        let mut server = Server::default();
+        open(
+            &mut server,
+            "class Circle {\n  class unit() { return Circle(); }\n  area: num { return 3; }\n}",
+        );
+        let got = request(&mut server, "textDocument/hover", 1, 9);
+        assert_eq!(got["contents"]["value"], "static method unit()");
+        let got = request(&mut server, "textDocument/hover", 2, 3);
+        assert_eq!(got["contents"]["value"], "method area: num");
+    }
+
+    #[test]
+    fn test_type_diagnostics() {
+        let mut server = Server::default();
+        let replies = open(&mut server, "var n: num = 1;\nvar s: text = n;");
+        let diagnostics = replies[0]["params"]["diagnostics"].as_array().unwrap();
+        let got: Vec<_> = diagnostics.iter().map(|d| &d["message"]).collect();
+        assert_eq!(got, vec!["[line 2] Error at 'text': unknown type"]);
+        assert_eq!(diagnostics[0]["range"], range(1, 7, 11));
+    }
+
+    #[test]
+    fn test_document_symbols() {
+        let mut server = Server::default();
+        open(&mut server, SCRIPT);
+        let got = request(&mut server, "textDocument/documentSymbol", 0, 0);
+        let names: Vec<_> = got
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| {
+                (
+                    s["name"].clone(),
+                    s["kind"].clone(),
+                    s["children"].as_array().unwrap().len(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                (json!("add"), json!(SYMBOL_FUNCTION), 0),
+                (json!("Point"), json!(SYMBOL_CLASS), 1),
+            ]
+        );
+        assert_eq!(got[1]["children"][0]["name"], "init");
+        assert_eq!(got[0]["range"]["start"]["line"], 1);
+        assert_eq!(got[0]["range"]["end"]["line"], 5);
+    }
+
+    #[test]
+    fn test_completion() {
+        let mut server = Server::default();
+        open(&mut server, SCRIPT);
+        let labels = |got: Json| -> Vec<String> {
+            got.as_array()
+                .unwrap()
+                .iter()
+                .map(|item| item["label"].as_str().unwrap().to_owned())
+                .collect()
+        };
+        // inside add, after sum is declared
+        let got = request(&mut server, "textDocument/completion", 3, 2);
+        assert_eq!(
+            labels(got),
+            vec![
+                "a",
+                "b",
+                "sum",
+                "total",
+                "add",
+                "Point",
+                "clock",
+                "getEnv",
+                "readFile",
+                "writeFile",
+                "type",
+                "fields",
+                "has_field",
+                "get_field",
+                "set_field",
+                "methods",
+                "define_method",
+                "superclass",
+                "arity",
+                "name"
+            ]
+        );
+        // at the top level
+        let got = request(&mut server, "textDocument/completion", 11, 0);
+        assert_eq!(labels(got)[..4], ["total", "add", "Point", "clock"]);
+    }
+}
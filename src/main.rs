@@ -1,44 +1,40 @@
-#![feature(hash_raw_entry)]
-#![feature(let_chains)]
+use std::cell::RefCell;
 use std::fs::File;
 use std::io;
 use std::io::stdin;
 use std::io::BufRead;
+use std::io::BufReader;
 use std::io::Read;
 use std::io::Write;
+use std::rc::Rc;
+use std::thread;
 
-mod callable;
-mod class;
-mod environment;
-mod error;
-mod expr;
-mod expr_eval;
-mod interpreter;
-mod models;
-mod parser;
-mod resolver;
-mod scanner;
-mod stmt;
-mod stmt_eval;
-mod token;
-mod value;
-
-use crate::error::LoxError;
-use crate::error::MainError;
-use crate::interpreter::Interpreter;
-use crate::parser::Parser;
-use crate::resolver::Resolver;
-use crate::scanner::Scanner;
+use rlox1::checker::Checker;
+use rlox1::debugger::Debugger;
+use rlox1::error::LoxError;
+use rlox1::error::MainError;
+use rlox1::formatter::format_source;
+use rlox1::interpreter::Interpreter;
+use rlox1::jlox;
+use rlox1::parser::Parser;
+use rlox1::profiler::Profiler;
+use rlox1::resolver::Resolver;
+use rlox1::scanner::Scanner;
 
 type MainResult = Result<(), MainError>;
 
-fn run(int: &mut Interpreter, src: &str) -> MainResult {
+// Lox calls recurse on the native stack, so leave room for the default call
+// depth limit, even in debug builds.
+const STACK_SIZE: usize = 64 << 20;
+
+fn run(int: &mut Interpreter, checker: &mut Checker, src: &str) -> MainResult {
     let mut scanner = Scanner::new(src);
     let tokens = scanner.scan_tokens()?;
     let mut parser = Parser::new(&tokens);
     let stmts = parser.parse()?;
     let mut resolver = Resolver::default();
     let resolutions = resolver.resolve(&stmts).map_err(LoxError::from)?;
+    checker.check(&stmts).map_err(LoxError::from)?;
     int.resolutions = resolutions;
 
     Ok(int.interpret(&stmts).map_err(LoxError::from)?)
@@ -46,6 +42,8 @@ fn run(int: &mut Interpreter, src: &str) -> MainResult {
 }
 
 fn run_prompt(int: &mut Interpreter) -> MainResult {
+    // kept for the session, so each line is checked against the ones before
+    let mut checker = Checker::default();
     let mut input = String::new();
     loop {
         input.clear();
@@ -56,32 +54,145 @@ fn run_prompt(int: &mut Interpreter) -> MainResult {
             return Ok(());
         }
         // error logging is handled by run
-        if let Err(err) = run(int, &input) {
-            eprintln!("{err}");
+        if let Err(err) = run(int, &mut checker, &input) {
+            writeln!(int.stderr, "{err}")?;
         }
     }
 }
 
-fn run_file(int: &mut Interpreter, file_name: &str) -> MainResult {
+// Reports why a script failed and exits with the code jlox would use, in
+// jlox's words if asked to.
+fn fail(int: &mut Interpreter, err: MainError, jlox: bool) -> MainResult {
+    match &err {
+        MainError::LoxError(err) if jlox => writeln!(int.stderr, "{}", jlox::report(err))?,
+        _ => writeln!(int.stderr, "{err}")?,
+    }
+    std::process::exit(err.exit_code());
+}
+
+fn run_file(int: &mut Interpreter, file_name: &str, jlox: bool) -> MainResult {
     let mut file = File::open(file_name)?;
     let mut contents = String::new();
     file.read_to_string(&mut contents)?;
-    if let Err(err) = run(int, &contents) {
-        eprintln!("{err}");
-        std::process::exit(75);
+    if let Err(err) = run(int, &mut Checker::default(), &contents) {
+        return fail(int, err, jlox);
+    }
+    Ok(())
+}
+
+fn run_debug(int: &mut Interpreter, file_name: &str) -> MainResult {
+    let contents = std::fs::read_to_string(file_name)?;
+    let debugger = Debugger::new(&contents, BufReader::new(stdin()), io::stdout());
+    int.hook = Some(Box::new(debugger));
+    if let Err(err) = run(int, &mut Checker::default(), &contents) {
+        return fail(int, err, false);
+    }
+    Ok(())
+}
+
+// Prints a report to stderr, and optionally writes folded stacks for
+// flamegraph tools.
+fn run_profile(int: &mut Interpreter, file_name: &str, folded: Option<&str>) -> MainResult {
+    let contents = std::fs::read_to_string(file_name)?;
+    let profiler = Rc::new(RefCell::new(Profiler::default()));
+    int.hook = Some(Box::new(profiler.clone()));
+    let result = run(int, &mut Checker::default(), &contents);
+    let mut profiler = profiler.borrow_mut();
+    profiler.finish();
+    write!(int.stderr, "{}", profiler.report())?;
+    if let Some(path) = folded {
+        std::fs::write(path, profiler.folded())?;
+    }
+    if let Err(err) = result {
+        return fail(int, err, false);
+    }
+    Ok(())
+}
+
+fn run_fmt(args: &[String]) -> MainResult {
+    let check = args.iter().any(|arg| arg == "--check");
+    let files: Vec<_> = args.iter().filter(|arg| *arg != "--check").collect();
+    if files.is_empty() {
+        let mut contents = String::new();
+        io::stdin().read_to_string(&mut contents)?;
+        let formatted = format_source(&contents)?;
+        if check {
+            if formatted != contents {
+                eprintln!("<stdin> is not formatted");
+                std::process::exit(1);
+            }
+        } else {
+            print!("{formatted}");
+        }
+        return Ok(());
+    }
+    let mut unformatted = false;
+    for file_name in files {
+        let contents = std::fs::read_to_string(file_name)?;
+        let formatted = match format_source(&contents) {
+            Ok(formatted) => formatted,
+            Err(err) => {
+                eprintln!("{file_name}:\n{err}");
+                std::process::exit(65);
+            }
+        };
+        if formatted == contents {
+            continue;
+        }
+        if check {
+            eprintln!("{file_name} is not formatted");
+            unformatted = true;
+        } else {
+            std::fs::write(file_name, formatted)?;
+        }
+    }
+    if unformatted {
+        std::process::exit(1);
     }
     Ok(())
 }
 
-fn main() -> MainResult {
+fn lox_main() -> MainResult {
     let args: Vec<String> = std::env::args().collect();
-    let mut interpreter = Interpreter::default();
+    if args.get(1).is_some_and(|arg| arg == "fmt") {
+        return run_fmt(&args[2..]);
+    }
+    let mut interpreter = Interpreter::builder().allow_all().build();
+    if args.len() == 3 && args[1] == "debug" {
+        return run_debug(&mut interpreter, &args[2]);
+    }
+    if args.len() == 3 && args[1] == "--jlox" {
+        return run_file(&mut interpreter, &args[2], true);
+    }
+    if args.len() == 3 && (args[1] == "--profile" || args[1].starts_with("--profile=")) {
+        let folded = args[1].strip_prefix("--profile=");
+        return run_profile(&mut interpreter, &args[2], folded);
+    }
     match args.len() {
         1 => run_prompt(&mut interpreter),
-        2 => run_file(&mut interpreter, &args[1]),
+        2 => run_file(&mut interpreter, &args[1], false),
         _ => {
-            eprintln!("usage: {} [script]", args[0]);
+            eprintln!(
+                "usage: {} [[--jlox | --profile[=stacks.folded]] script] | debug script | fmt [--check] [files...]",
+                args[0]
+            );
             std::process::exit(64);
         }
     }
 }
+
+fn main() {
+    let lox = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(|| {
+            // errors hold Rc values, so they are reported from this thread
+            if let Err(err) = lox_main() {
+                eprintln!("Error: {err:?}");
+                std::process::exit(1);
+            }
+        })
+        .expect("must be able to spawn the interpreter thread");
+    if lox.join().is_err() {
+        std::process::exit(101);
+    }
+}
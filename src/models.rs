@@ -2,6 +2,7 @@ pub use crate::expr::Expr;
 pub use crate::stmt::FunDecl;
 pub use crate::stmt::Stmt;
 pub use crate::stmt::StmtList;
+pub use crate::token::Comment;
 pub use crate::token::Token;
 pub use crate::token::TokenType;
 pub use crate::value::Value;
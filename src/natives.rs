@@ -0,0 +1,552 @@
+use crate::callable::LoxCallable;
+use crate::callable::LoxFunction;
+use crate::class::LoxClass;
+use crate::class::LoxInstance;
+use crate::environment::Env;
+use crate::environment::Environment;
+use crate::error::RuntimeError;
+use crate::interpreter::Interpreter;
+use crate::value::Value;
+use compact_str::CompactString;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+use std::time;
+
+type NativeFn = fn(&mut Interpreter, Vec<Value>) -> Result<Value, RuntimeError>;
+
+// A function implemented in Rust. Anything touching the host checks the
+// interpreter's capabilities first.
+#[derive(Clone, Copy)]
+pub struct Native {
+    pub name: &'static str,
+    arity: usize,
+    function: NativeFn,
+}
+
+pub const NATIVES: &[Native] = &[
+    Native {
+        name: "clock",
+        arity: 0,
+        function: clock,
+    },
+    Native {
+        name: "getEnv",
+        arity: 1,
+        function: get_env,
+    },
+    Native {
+        name: "readFile",
+        arity: 1,
+        function: read_file,
+    },
+    Native {
+        name: "writeFile",
+        arity: 2,
+        function: write_file,
+    },
+    Native {
+        name: "type",
+        arity: 1,
+        function: type_of,
+    },
+    Native {
+        name: "fields",
+        arity: 1,
+        function: fields,
+    },
+    Native {
+        name: "has_field",
+        arity: 2,
+        function: has_field,
+    },
+    Native {
+        name: "get_field",
+        arity: 2,
+        function: get_field,
+    },
+    Native {
+        name: "set_field",
+        arity: 3,
+        function: set_field,
+    },
+    Native {
+        name: "methods",
+        arity: 1,
+        function: methods,
+    },
+    Native {
+        name: "define_method",
+        arity: 3,
+        function: define_method,
+    },
+    Native {
+        name: "superclass",
+        arity: 1,
+        function: superclass,
+    },
+    Native {
+        name: "arity",
+        arity: 1,
+        function: arity,
+    },
+    Native {
+        name: "name",
+        arity: 1,
+        function: name,
+    },
+];
+
+pub fn define_natives(environment: &Environment) {
+    for native in NATIVES {
+        environment.define(native.name, Value::Callable(Rc::new(*native)));
+    }
+}
+
+impl fmt::Debug for Native {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Native({})", self.name)
+    }
+}
+
+impl fmt::Display for Native {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl LoxCallable for Native {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn call(&self, interpreter: &mut Interpreter, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        (self.function)(interpreter, args)
+    }
+}
+
+fn denied(interpreter: &Interpreter, action: String) -> RuntimeError {
+    RuntimeError::PermissionDenied {
+        line: interpreter.current_line(),
+        action: action.into(),
+    }
+}
+
+fn failed(interpreter: &Interpreter, native: &'static str, msg: impl fmt::Display) -> RuntimeError {
+    RuntimeError::NativeError {
+        line: interpreter.current_line(),
+        native,
+        msg: format!("{msg}").into(),
+    }
+}
+
+fn string_arg(
+    interpreter: &Interpreter,
+    native: &'static str,
+    value: &Value,
+) -> Result<CompactString, RuntimeError> {
+    match value {
+        Value::VString(s) => Ok(s.clone()),
+        _ => Err(failed(
+            interpreter,
+            native,
+            format!("expected a string, got {value}"),
+        )),
+    }
+}
+
+fn instance_arg(
+    interpreter: &Interpreter,
+    native: &'static str,
+    value: &Value,
+) -> Result<Rc<LoxInstance>, RuntimeError> {
+    match value {
+        Value::Object(instance) => Ok(instance.clone()),
+        _ => Err(failed(
+            interpreter,
+            native,
+            format!("expected an instance, got {value}"),
+        )),
+    }
+}
+
+fn class_arg(
+    interpreter: &Interpreter,
+    native: &'static str,
+    value: &Value,
+) -> Result<Rc<LoxClass>, RuntimeError> {
+    match value {
+        Value::Class(class) => Ok(class.clone()),
+        _ => Err(failed(
+            interpreter,
+            native,
+            format!("expected a class, got {value}"),
+        )),
+    }
+}
+
+// Classes can be called too, to make an instance.
+fn callable_arg(
+    interpreter: &Interpreter,
+    native: &'static str,
+    value: &Value,
+) -> Result<Rc<dyn LoxCallable>, RuntimeError> {
+    match value {
+        Value::Callable(callable) => Ok(callable.clone()),
+        Value::Class(class) => Ok(Rc::new(class.clone())),
+        _ => Err(failed(
+            interpreter,
+            native,
+            format!("expected a function or class, got {value}"),
+        )),
+    }
+}
+
+// Lox has no lists, so names are listed in one string.
+fn name_list(
+    interpreter: &mut Interpreter,
+    names: Vec<CompactString>,
+) -> Result<Value, RuntimeError> {
+    let list = names.join(", ");
+    interpreter.track_string(list.len())?;
+    Ok(Value::VString(list.into()))
+}
+
+fn clock(interpreter: &mut Interpreter, _args: Vec<Value>) -> Result<Value, RuntimeError> {
+    if !interpreter.capabilities.time {
+        return Err(denied(interpreter, "reading the clock".into()));
+    }
+    let now = time::SystemTime::now();
+    let elapsed = now
+        .duration_since(time::UNIX_EPOCH)
+        .or(Err(RuntimeError::SystemTimeError {
+            line: interpreter.current_line(),
+        }))?;
+    Ok(Value::VNumber(elapsed.as_secs_f64()))
+}
+
+// nil when the variable isn't set
+fn get_env(interpreter: &mut Interpreter, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let name = string_arg(interpreter, "getEnv", &args[0])?;
+    if !interpreter.capabilities.env {
+        return Err(denied(
+            interpreter,
+            format!("reading environment variable {name}"),
+        ));
+    }
+    match env::var(name.as_str()) {
+        Ok(value) => {
+            interpreter.track_string(value.len())?;
+            Ok(Value::VString(value.into()))
+        }
+        Err(_) => Ok(Value::VNil),
+    }
+}
+
+fn read_file(interpreter: &mut Interpreter, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let path = string_arg(interpreter, "readFile", &args[0])?;
+    if !interpreter.capabilities.can_read(Path::new(path.as_str())) {
+        return Err(denied(interpreter, format!("reading {path}")));
+    }
+    let contents =
+        fs::read_to_string(path.as_str()).map_err(|err| failed(interpreter, "readFile", err))?;
+    interpreter.track_string(contents.len())?;
+    Ok(Value::VString(contents.into()))
+}
+
+fn write_file(interpreter: &mut Interpreter, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let path = string_arg(interpreter, "writeFile", &args[0])?;
+    let contents = string_arg(interpreter, "writeFile", &args[1])?;
+    if !interpreter.capabilities.can_write(Path::new(path.as_str())) {
+        return Err(denied(interpreter, format!("writing {path}")));
+    }
+    fs::write(path.as_str(), contents.as_str())
+        .map_err(|err| failed(interpreter, "writeFile", err))?;
+    Ok(Value::VNil)
+}
+
+fn type_of(_interpreter: &mut Interpreter, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    Ok(Value::VString(args[0].type_name().into()))
+}
+
+fn fields(interpreter: &mut Interpreter, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let instance = instance_arg(interpreter, "fields", &args[0])?;
+    name_list(interpreter, instance.field_names())
+}
+
+fn has_field(interpreter: &mut Interpreter, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let instance = instance_arg(interpreter, "has_field", &args[0])?;
+    let name = string_arg(interpreter, "has_field", &args[1])?;
+    Ok(Value::Bool(instance.field(&name).is_some()))
+}
+
+// Only fields, unlike `.`, which falls back to methods.
+fn get_field(interpreter: &mut Interpreter, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let instance = instance_arg(interpreter, "get_field", &args[0])?;
+    let name = string_arg(interpreter, "get_field", &args[1])?;
+    instance
+        .field(&name)
+        .ok_or_else(|| RuntimeError::UndefinedProperty {
+            line: interpreter.current_line(),
+            name,
+        })
+}
+
+// Returns the value, like an assignment.
+fn set_field(interpreter: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let instance = instance_arg(interpreter, "set_field", &args[0])?;
+    let name = string_arg(interpreter, "set_field", &args[1])?;
+    let value = args.pop().unwrap_or_default();
+    instance.set_field(&name, value.clone());
+    Ok(value)
+}
+
+fn methods(interpreter: &mut Interpreter, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let class = class_arg(interpreter, "methods", &args[0])?;
+    name_list(interpreter, class.method_names())
+}
+
+// Makes a function a method of the class, taking the instance it's called
+// on as its first parameter. Returns the class.
+fn define_method(interpreter: &mut Interpreter, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let class = class_arg(interpreter, "define_method", &args[0])?;
+    let name = string_arg(interpreter, "define_method", &args[1])?;
+    let callable = callable_arg(interpreter, "define_method", &args[2])?;
+    let function = match callable.as_function() {
+        Some(function) if !function.takes_this && function.arity() > 0 => function,
+        _ => {
+            return Err(failed(
+                interpreter,
+                "define_method",
+                format!("expected a function taking the instance first, got {callable}"),
+            ));
+        }
+    };
+    class.define_method(
+        &name,
+        LoxFunction {
+            takes_this: true,
+            ..function.clone()
+        },
+    );
+    Ok(Value::Class(class))
+}
+
+// nil for a class without one
+fn superclass(interpreter: &mut Interpreter, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let class = class_arg(interpreter, "superclass", &args[0])?;
+    Ok(match &class.parent {
+        Some(parent) => Value::Class(parent.clone()),
+        None => Value::VNil,
+    })
+}
+
+fn arity(interpreter: &mut Interpreter, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let callable = callable_arg(interpreter, "arity", &args[0])?;
+    Ok(Value::VNumber(callable.arity() as f64))
+}
+
+fn name(interpreter: &mut Interpreter, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    let callable = callable_arg(interpreter, "name", &args[0])?;
+    Ok(Value::VString(callable.name().into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::capabilities::Capabilities;
+    use crate::error::LoxResult;
+    use crate::interpreter::Interpreter;
+    use crate::output::SharedBuffer;
+    use crate::parser::Parser;
+    use crate::resolver::Resolver;
+    use crate::scanner::Scanner;
+    use std::env;
+    use std::fs;
+
+    fn sandboxed_eval(input: &str, interpreter: &mut Interpreter) -> LoxResult<String> {
+        let output = SharedBuffer::default();
+        interpreter.stdout = Box::new(output.clone());
+        let mut scanner = Scanner::new(input);
+        let tokens = scanner.scan_tokens()?;
+        let mut parser = Parser::new(&tokens);
+        let stmts = parser.parse()?;
+        let mut resolver = Resolver::default();
+        interpreter.resolutions = resolver.resolve(&stmts)?;
+        interpreter.interpret(&stmts)?;
+        Ok(output.contents())
+    }
+
+    #[rstest::rstest]
+    #[case("clock();", "[line 1] Error: permission denied: reading the clock")]
+    #[case("print 1;", "[line 1] Error: permission denied: print")]
+    #[case(
+        "\nvar home = getEnv(\"HOME\");",
+        "[line 2] Error: permission denied: reading environment variable HOME"
+    )]
+    #[case(
+        "readFile(\"/etc/passwd\");",
+        "[line 1] Error: permission denied: reading /etc/passwd"
+    )]
+    #[case(
+        "writeFile(\"/tmp/x\", \"y\");",
+        "[line 1] Error: permission denied: writing /tmp/x"
+    )]
+    #[case("readFile(4);", "[line 1] Error: readFile: expected a string, got 4")]
+    fn test_denied(#[case] input: &str, #[case] want: &str) {
+        let mut interpreter = Interpreter::builder().build();
+        let got = sandboxed_eval(input, &mut interpreter).expect_err("should be denied");
+        assert_eq!(format!("{got}"), want);
+    }
+
+    #[test]
+    fn test_reflection() -> LoxResult<()> {
+        let input = r#"
+class Shape {
+  init(sides) {
+    this.sides = sides;
+  }
+  area() {}
+}
+class Square < Shape {
+  init() {
+    super.init(4);
+    this.side = 1;
+  }
+  perimeter() {}
+}
+var s = Square();
+print type(s);
+print type(Square);
+print type(1);
+print fields(s);
+print has_field(s, "side");
+print has_field(s, "area");
+print set_field(s, "side", 2);
+print get_field(s, "side");
+print methods(Square);
+print superclass(Square);
+print superclass(Shape);
+print arity(Shape);
+print arity(clock);
+print name(s.perimeter);
+print name(Square);
+"#;
+        let mut interpreter = Interpreter::builder().allow_stdout().build();
+        let got = sandboxed_eval(input, &mut interpreter)?;
+        assert_eq!(
+            got,
+            "instance
+class
+number
+side, sides
+true
+false
+2
+2
+area, init, perimeter
+Shape
+nil
+1
+0
+perimeter
+Square
+"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_define_method() -> LoxResult<()> {
+        let input = r#"
+class Point {
+  init(x, y) {
+    this.x = x;
+    this.y = y;
+  }
+}
+var p = Point(1, 2);
+define_method(Point, "sum", fun (self) { return self.x + self.y; });
+define_method(Point, "scaled", fun (self, k) { return Point(self.x * k, self.y * k); });
+print p.sum();
+print p.scaled(3).sum();
+print methods(Point);
+print arity(p.scaled);
+"#;
+        let mut interpreter = Interpreter::builder().allow_stdout().build();
+        let got = sandboxed_eval(input, &mut interpreter)?;
+        assert_eq!(got, "3\n9\ninit, scaled, sum\n1\n");
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case("fields(1);", "[line 1] Error: fields: expected an instance, got 1")]
+    #[case(
+        "class A {}\nget_field(A(), \"x\");",
+        "[line 2] Error: undefined property: 'x'"
+    )]
+    #[case(
+        "class A {}\nhas_field(A(), nil);",
+        "[line 2] Error: has_field: expected a string, got nil"
+    )]
+    #[case(
+        "superclass(\"A\");",
+        "[line 1] Error: superclass: expected a class, got A"
+    )]
+    #[case(
+        "class A {}\ndefine_method(A, \"m\", clock);",
+        "[line 2] Error: define_method: expected a function taking the instance first, got clock"
+    )]
+    #[case(
+        "arity(nil);",
+        "[line 1] Error: arity: expected a function or class, got nil"
+    )]
+    fn test_reflection_errors(#[case] input: &str, #[case] want: &str) {
+        let mut interpreter = Interpreter::builder().allow_stdout().build();
+        let got = sandboxed_eval(input, &mut interpreter).expect_err("should fail");
+        assert_eq!(format!("{got}"), want);
+    }
+
+    #[test]
+    fn test_granted() -> LoxResult<()> {
+        let dir = env::temp_dir().join(format!("rlox1-natives-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("must create test dir");
+        let file = dir.join("out.txt");
+        let input = format!(
+            r#"
+writeFile("{0}", "hello");
+print readFile("{0}");
+print getEnv("RLOX1_SURELY_UNSET");
+print clock() > 0;
+"#,
+            file.display()
+        );
+        let mut interpreter = Interpreter::builder()
+            .allow_read(&dir)
+            .allow_write(&dir)
+            .allow_env()
+            .allow_time()
+            .allow_stdout()
+            .build();
+        let got = sandboxed_eval(&input, &mut interpreter)?;
+        assert_eq!(got, "hello\nnil\ntrue\n");
+
+        let missing = format!("readFile(\"{}\");", dir.join("missing").display());
+        let got = sandboxed_eval(&missing, &mut interpreter).expect_err("should fail");
+        assert!(format!("{got}").starts_with("[line 1] Error: readFile: No such file"));
+        fs::remove_dir_all(&dir).expect("must clean up test dir");
+
+        let mut interpreter = Interpreter::default();
+        assert_eq!(interpreter.capabilities.read, Capabilities::default().read);
+        assert_eq!(
+            sandboxed_eval("print clock() > 0;", &mut interpreter)?,
+            "true\n"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+use std::cell::RefCell;
+use std::io;
+use std::io::Write;
+use std::rc::Rc;
+
+// An output sink that can be handed to an interpreter and read back later,
+// e.g. to capture what a script prints.
+#[derive(Debug, Clone, Default)]
+pub struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl SharedBuffer {
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+
+    pub fn clear(&self) {
+        self.0.borrow_mut().clear();
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::LoxError;
+    use crate::error::LoxResult;
+    use crate::error::RuntimeError;
+    use crate::interpreter::Interpreter;
+    use crate::parser::Parser;
+    use crate::resolver::Resolver;
+    use crate::scanner::Scanner;
+
+    struct Closed;
+
+    impl Write for Closed {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn run(input: &str, interpreter: &mut Interpreter) -> LoxResult<()> {
+        let mut scanner = Scanner::new(input);
+        let tokens = scanner.scan_tokens()?;
+        let mut parser = Parser::new(&tokens);
+        let stmts = parser.parse()?;
+        let mut resolver = Resolver::default();
+        interpreter.resolutions = resolver.resolve(&stmts)?;
+        Ok(interpreter.interpret(&stmts)?)
+    }
+
+    #[test]
+    fn test_sinks() -> LoxResult<()> {
+        let stdout = SharedBuffer::default();
+        let stderr = SharedBuffer::default();
+        let mut interpreter = Interpreter::builder()
+            .allow_stdout()
+            .stdout(stdout.clone())
+            .stderr(stderr.clone())
+            .build();
+        run("print \"one\";\nprint 2;", &mut interpreter)?;
+        writeln!(interpreter.stderr, "oops").expect("writes should not fail");
+        assert_eq!(stdout.contents(), "one\n2\n");
+        assert_eq!(stderr.contents(), "oops\n");
+
+        stdout.clear();
+        run("print 3;", &mut interpreter)?;
+        assert_eq!(stdout.contents(), "3\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_error() {
+        let mut interpreter = Interpreter::builder().allow_stdout().stdout(Closed).build();
+        let got = run("\nprint 1;", &mut interpreter);
+        assert!(matches!(
+            got,
+            Err(LoxError::RuntimeError(RuntimeError::OutputError { line, .. })) if line == "2"
+        ));
+    }
+}
@@ -15,11 +15,18 @@ type ParseExpr = Result<Expr, ParseError>;
 type ParseStmt = Result<Stmt, ParseError>;
 use crate::error::LoxError;
 
+// After this many errors the rest are likely cascades of the first few.
+const MAX_ERRORS: usize = 20;
+
 pub struct Parser<'long> {
     tokens: &'long [Token],
     current: usize,
     statements: Vec<Stmt>,
     errors: Vec<ParseError>,
+    // how many blocks we are inside of, so recovery doesn't eat their '}'
+    depth: usize,
+    // set when an argument was skipped, so its statement is dropped as an error
+    dropped_argument: bool,
 }
 
 impl<'long> Parser<'long> {
@@ -29,20 +36,34 @@ impl<'long> Parser<'long> {
             current: 0,
             statements: vec![],
             errors: vec![],
+            depth: 0,
+            dropped_argument: false,
         }
     }
 
     pub fn parse(&mut self) -> Result<StmtList, LoxError> {
-        while !self.is_at_end() {
-            self.line();
-        }
-        if !self.errors.is_empty() {
-            Err(mem::take(&mut self.errors))?
+        let (stmts, errors) = self.parse_partial();
+        if !errors.is_empty() {
+            Err(errors)?
         } else {
-            Ok(StmtList(mem::take(&mut self.statements)))
+            Ok(stmts)
         }
     }
 
+    // Parses as much as possible for tooling. Statements that failed to parse
+    // are replaced by Stmt::Error, so later passes can still run over the rest.
+    pub fn parse_partial(&mut self) -> (StmtList, Vec<ParseError>) {
+        while !self.is_at_end() && !self.gave_up() {
+            self.line();
+        }
+        let stmts = StmtList {
+            stmts: mem::take(&mut self.statements),
+            end_line: self.current().line,
+            from_for: false,
+        };
+        (stmts, mem::take(&mut self.errors))
+    }
+
     fn advance(&mut self) {
         self.current += 1
     }
@@ -71,15 +92,35 @@ impl<'long> Parser<'long> {
         }
     }
 
-    fn consume(&mut self, token_type: TokenType, err_msg: &str) -> Result<(), ParseError> {
-        let token = self.current();
-        if token.token != token_type {
-            return Err(ParseError::from((token.clone(), err_msg)));
+    fn consume(&mut self, token_type: TokenType, context: &'static str) -> Result<(), ParseError> {
+        if self.current().token != token_type {
+            return Err(self.expected(&[token_type], context));
         }
         self.advance();
         Ok(())
     }
 
+    fn expected(&self, expected: &[TokenType], context: &'static str) -> ParseError {
+        ParseError::Expected {
+            expected: expected.to_vec(),
+            found: self.current().clone(),
+            context,
+        }
+    }
+
+    fn report(&mut self, err: ParseError) {
+        if self.errors.len() < MAX_ERRORS {
+            self.errors.push(err);
+        } else if !self.gave_up() {
+            let line = self.current().line;
+            self.errors.push(ParseError::TooManyErrors(line));
+        }
+    }
+
+    fn gave_up(&self) -> bool {
+        self.errors.len() > MAX_ERRORS
+    }
+
     fn check(&self, token_type: &TokenType) -> bool {
         if self.is_at_end() {
             false
@@ -88,6 +129,12 @@ impl<'long> Parser<'long> {
         }
     }
 
+    fn check_next(&self, token_type: &TokenType) -> bool {
+        self.tokens
+            .get(self.current + 1)
+            .is_some_and(|token| token.token == *token_type)
+    }
+
     fn token_match(&mut self, types: &'static [TokenType]) -> bool {
         for token_type in types {
             if self.check(token_type) {
@@ -99,88 +146,192 @@ impl<'long> Parser<'long> {
     }
 
     fn line(&mut self) {
-        match self.declaration() {
+        let stmt = self.recovering_declaration();
+        self.statements.push(stmt);
+    }
+
+    fn recovering_declaration(&mut self) -> Stmt {
+        let line = self.current().line;
+        // a statement nested in this one only answers for its own arguments
+        let outer = mem::take(&mut self.dropped_argument);
+        let stmt = self.declaration();
+        let dropped_argument = mem::replace(&mut self.dropped_argument, outer);
+        match stmt {
             Err(err) => {
-                self.errors.push(err);
+                self.report(err);
                 self.synchronize();
+                Stmt::Error(line)
             }
-            Ok(stmt) => self.statements.push(stmt),
-        };
+            // the call is missing an argument, so later passes would misjudge it
+            Ok(_) if dropped_argument => Stmt::Error(line),
+            Ok(stmt) => stmt,
+        }
     }
 
     fn declaration(&mut self) -> ParseStmt {
-        if self.token_match(&[Fun]) {
+        // `fun (` starts an anonymous function, so that's an expression
+        if self.check(&Fun) && !self.check_next(&LeftParen) {
+            self.advance();
             self.fun_declaration().map(Stmt::FunDecl)
         } else if self.token_match(&[Var]) {
             self.var_declaration()
         } else if self.token_match(&[Class]) {
             self.class_declaration()
+        } else if self.token_match(&[Trait]) {
+            self.trait_declaration()
         } else {
             self.statement()
         }
     }
 
     fn class_declaration(&mut self) -> ParseStmt {
-        self.consume(Identifier, "Expected identifier in declaration")?;
+        self.consume(Identifier, "for class name")?;
         let name = self.previous();
         let line = self.current_line();
         let parent = if self.token_match(&[Less]) {
-            self.consume(Identifier, "Expected identifier for parent class")?;
+            self.consume(Identifier, "for superclass name")?;
             Some(Expr::Variable(self.previous()))
         } else {
             None
         };
-        self.consume(LeftBrace, "Expected '{' to start class declaration")?;
-        let mut methods = vec![];
-        while !self.check(&RightBrace) && !self.is_at_end() {
-            let method = self.fun_declaration()?;
-            methods.push(method);
+        let mut traits = vec![];
+        if self.token_match(&[With]) {
+            loop {
+                self.consume(Identifier, "for trait name")?;
+                traits.push(Expr::Variable(self.previous()));
+                if !self.token_match(&[Comma]) {
+                    break;
+                }
+            }
         }
-        self.consume(RightBrace, "Expected '}' to end class declaration")?;
+        self.consume(LeftBrace, "before class body")?;
+        let methods = self.methods("after class body")?;
         Ok(Stmt::ClassDecl {
             line,
             name,
             parent,
+            traits,
+            methods,
+            end_line: self.current_line(),
+        })
+    }
+
+    fn trait_declaration(&mut self) -> ParseStmt {
+        self.consume(Identifier, "for trait name")?;
+        let name = self.previous();
+        let line = self.current_line();
+        self.consume(LeftBrace, "before trait body")?;
+        let methods = self.methods("after trait body")?;
+        Ok(Stmt::TraitDecl {
+            line,
+            name,
             methods,
+            end_line: self.current_line(),
         })
     }
 
+    // The methods of a class or trait body, up to its closing '}'.
+    fn methods(&mut self, context: &'static str) -> Result<Vec<FunDecl>, ParseError> {
+        let mut methods = vec![];
+        while !self.check(&RightBrace) && !self.is_at_end() {
+            methods.push(self.method()?);
+        }
+        self.consume(RightBrace, context)?;
+        Ok(methods)
+    }
+
+    // A method is static when prefixed with `class`, and a getter when it
+    // has no parameter list.
+    fn method(&mut self) -> Result<FunDecl, ParseError> {
+        let is_static = self.token_match(&[Class]);
+        self.consume(Identifier, "for function name")?;
+        let name = self.previous();
+        let line = self.current_line();
+        match self.peek() {
+            LeftParen => {
+                self.advance();
+                Ok(FunDecl {
+                    is_static,
+                    ..self.function(name, line)?
+                })
+            }
+            LeftBrace | Colon => {
+                let return_type = self.annotation()?;
+                self.consume(LeftBrace, "before getter body")?;
+                Ok(FunDecl {
+                    line,
+                    name,
+                    parameters: vec![],
+                    parameter_types: vec![],
+                    return_type,
+                    body: self.block()?.into(),
+                    is_static,
+                    is_getter: true,
+                })
+            }
+            _ => Err(self.expected(&[LeftParen, LeftBrace], "after method name")),
+        }
+    }
+
     fn fun_declaration(&mut self) -> Result<FunDecl, ParseError> {
-        self.consume(Identifier, "Expected identifier in declaration")?;
+        self.consume(Identifier, "for function name")?;
         let name = self.previous();
         let line = self.current_line();
-        self.consume(LeftParen, "Expected '(' to start parameter list")?;
-        let parameters = self.parameters()?;
-        self.consume(LeftBrace, "Expected '{' to start function body")?;
+        self.consume(LeftParen, "after function name")?;
+        self.function(name, line)
+    }
+
+    // The parameters, return type and body of a function, after its '('.
+    fn function(&mut self, name: Token, line: usize) -> Result<FunDecl, ParseError> {
+        let (parameters, parameter_types) = self.parameters()?;
+        let return_type = self.annotation()?;
+        self.consume(LeftBrace, "before function body")?;
         let body = self.block()?.into();
         Ok(FunDecl {
             line,
             name,
             parameters,
+            parameter_types,
+            return_type,
             body,
+            is_static: false,
+            is_getter: false,
         })
     }
 
+    // An optional `: type` after a name or parameter list.
+    fn annotation(&mut self) -> Result<Option<Token>, ParseError> {
+        if !self.token_match(&[Colon]) {
+            return Ok(None);
+        }
+        // `nil` is a keyword, but also the type of nil
+        if !self.token_match(&[Nil]) {
+            self.consume(Identifier, "for type name")?;
+        }
+        Ok(Some(self.previous()))
+    }
+
     fn var_declaration(&mut self) -> ParseStmt {
-        self.consume(Identifier, "expected identifier in declaration")?;
+        let line = self.current_line();
+        self.consume(Identifier, "for variable name")?;
         let lhs = self.previous();
+        let annotation = self.annotation()?;
         let rhs: Option<Expr> = if self.token_match(&[Equal]) {
             Some(self.expression()?)
         } else {
             None
         };
-        self.consume(Semicolon, "Expected ';' after variable declaration")?;
-        let line = self.current_line();
-        Ok(Stmt::VarDecl(line, lhs, rhs))
+        self.consume(Semicolon, "after variable declaration")?;
+        Ok(Stmt::VarDecl(line, lhs, annotation, rhs))
     }
 
     fn return_statement(&mut self) -> ParseStmt {
         let line = self.current_line();
         if self.token_match(&[Semicolon]) {
-            Ok(Stmt::Return(line, Expr::Literal(Value::VNil)))
+            Ok(Stmt::Return(line, Expr::Literal(Value::VNil, None)))
         } else {
             let expr = self.expression()?;
-            self.consume(Semicolon, "Expected ';' after return statement")?;
+            self.consume(Semicolon, "after return value")?;
             Ok(Stmt::Return(line, expr))
         }
     }
@@ -208,8 +359,8 @@ impl<'long> Parser<'long> {
     }
 
     fn for_statement(&mut self) -> ParseStmt {
-        self.consume(LeftParen, "Expect '(' around condition")?;
         let line = self.current_line();
+        self.consume(LeftParen, "after 'for'")?;
 
         let init_stmt = if self.token_match(&[Semicolon]) {
             None
@@ -220,10 +371,10 @@ impl<'long> Parser<'long> {
         };
 
         let end_expr = if self.token_match(&[Semicolon]) {
-            Expr::Literal(Value::Bool(true))
+            Expr::Literal(Value::Bool(true), None)
         } else {
             let expr = self.expression()?;
-            self.consume(Semicolon, "Expect ';' in for condition (end)")?;
+            self.consume(Semicolon, "after loop condition")?;
             expr
         };
 
@@ -231,39 +382,46 @@ impl<'long> Parser<'long> {
             None
         } else {
             let expr = self.expression()?;
-            self.consume(RightParen, "Expect ')' in for condition (update)")?;
+            self.consume(RightParen, "after for clauses")?;
             Some(expr)
         };
 
         let base_body = self.statement()?;
         let body = match update_expr {
             None => base_body,
-            Some(update_expr) => {
-                Stmt::Block(StmtList(vec![base_body, Stmt::Expr(line, update_expr)]))
-            }
+            Some(update_expr) => Stmt::Block(StmtList {
+                stmts: vec![base_body, Stmt::Expr(line, update_expr)],
+                end_line: self.current_line(),
+                from_for: true,
+            }),
         };
 
         let while_stmt = Stmt::While(line, end_expr, Box::new(body));
 
         Ok(match init_stmt {
             None => while_stmt,
-            Some(init_stmt) => Stmt::Block(StmtList(vec![init_stmt, while_stmt])),
+            Some(init_stmt) => Stmt::Block(StmtList {
+                stmts: vec![init_stmt, while_stmt],
+                end_line: self.current_line(),
+                from_for: true,
+            }),
         })
     }
 
     fn while_statement(&mut self) -> ParseStmt {
-        self.consume(LeftParen, "Expect '(' around condition")?;
+        let line = self.current_line();
+        self.consume(LeftParen, "after 'while'")?;
         let expr = self.expression()?;
-        self.consume(RightParen, "Expect ')' around condition")?;
+        self.consume(RightParen, "after condition")?;
         let stmt = Box::new(self.statement()?);
-        let line = self.current_line();
         Ok(Stmt::While(line, expr, stmt))
     }
 
     fn if_statement(&mut self) -> ParseStmt {
-        self.consume(LeftParen, "Expect '(' around condition")?;
+        let line = self.current_line();
+        self.consume(LeftParen, "after 'if'")?;
         let if_expr = self.expression()?;
-        self.consume(RightParen, "Expect ')' around condition")?;
+        self.consume(RightParen, "after if condition")?;
         let then_stmt = Box::new(self.declaration()?);
 
         let else_stmt = if self.token_match(&[Else]) {
@@ -271,7 +429,6 @@ impl<'long> Parser<'long> {
         } else {
             None
         };
-        let line = self.current_line();
         Ok(Stmt::IfThenElse {
             line,
             if_expr,
@@ -281,26 +438,33 @@ impl<'long> Parser<'long> {
     }
 
     fn print_statement(&mut self) -> ParseStmt {
-        let expr = self.expression()?;
-        self.consume(Semicolon, "Expect ';' after value.")?;
         let line = self.current_line();
+        let expr = self.expression()?;
+        self.consume(Semicolon, "after value")?;
         Ok(Stmt::Print(line, expr))
     }
 
+    // Errors inside a block are reported and recovered from here, so one bad
+    // statement in a function body doesn't throw away the rest of it.
     fn block(&mut self) -> Result<StmtList, ParseError> {
         let mut statements = vec![];
-        while !self.check(&RightBrace) && !self.is_at_end() {
-            let decl = self.declaration()?;
-            statements.push(decl);
+        self.depth += 1;
+        while !self.check(&RightBrace) && !self.is_at_end() && !self.gave_up() {
+            statements.push(self.recovering_declaration());
         }
-        self.consume(RightBrace, "Expect '}' to end block.")?;
-        Ok(StmtList(statements))
+        self.depth -= 1;
+        self.consume(RightBrace, "after block")?;
+        Ok(StmtList {
+            stmts: statements,
+            end_line: self.current_line(),
+            from_for: false,
+        })
     }
 
     fn expression_statement(&mut self) -> ParseStmt {
+        let line = self.current().line;
         let expr = self.expression()?;
-        self.consume(Semicolon, "Expect ';' after value.")?;
-        let line = self.current_line();
+        self.consume(Semicolon, "after expression")?;
         Ok(Stmt::Expr(line, expr))
     }
 
@@ -361,10 +525,7 @@ impl<'long> Parser<'long> {
                     name,
                     value: Box::new(value),
                 }),
-                _ => Err(ParseError::from((
-                    equals.clone(),
-                    "Invalid assignment target.",
-                ))),
+                _ => Err(ParseError::InvalidAssignmentTarget { equals }),
             }
         } else {
             Ok(expr)
@@ -384,7 +545,7 @@ impl<'long> Parser<'long> {
     }
 
     fn comparison(&mut self) -> ParseExpr {
-        self.bin_op(&[Greater, GreaterEqual, Less, LessEqual], |s| s.term())
+        self.bin_op(&[Greater, GreaterEqual, Less, LessEqual, Is], |s| s.term())
     }
 
     fn term(&mut self) -> ParseExpr {
@@ -408,26 +569,30 @@ impl<'long> Parser<'long> {
         }
     }
 
-    fn parameters(&mut self) -> Result<Vec<Token>, ParseError> {
+    fn parameters(&mut self) -> Result<(Vec<Token>, Vec<Option<Token>>), ParseError> {
         if self.token_match(&[RightParen]) {
-            return Ok(vec![]);
+            return Ok((vec![], vec![]));
         }
         let mut params = vec![];
+        let mut types = vec![];
         loop {
-            self.consume(Identifier, "Expected identifier for parameter")?;
+            self.consume(Identifier, "for parameter name")?;
             params.push(self.previous());
+            types.push(self.annotation()?);
             if self.token_match(&[RightParen]) {
                 break;
             }
-            self.consume(Comma, "Expected ',' between parameters")?;
+            if !self.token_match(&[Comma]) {
+                return Err(self.expected(&[Comma, RightParen], "after parameter"));
+            }
         }
         if params.len() >= 255 {
-            Err(ParseError::from((
-                self.current().clone(),
-                "Can't have more than 255 parameters",
-            )))
+            Err(ParseError::TooMany {
+                found: self.current().clone(),
+                what: "parameters",
+            })
         } else {
-            Ok(params)
+            Ok((params, types))
         }
     }
 
@@ -437,22 +602,51 @@ impl<'long> Parser<'long> {
         }
         let mut args = vec![];
         loop {
-            args.push(self.expression()?);
+            match self.expression() {
+                Ok(arg) => args.push(arg),
+                Err(err) => {
+                    self.report(err);
+                    self.dropped_argument = true;
+                    if !self.skip_argument() {
+                        // the list never closes; let the statement carry on
+                        // from wherever we stopped
+                        return Ok(args);
+                    }
+                }
+            }
             if self.token_match(&[RightParen]) {
                 break;
             }
-            self.consume(Comma, "Expected ',' between arguments")?;
+            if !self.token_match(&[Comma]) {
+                return Err(self.expected(&[Comma, RightParen], "after argument"));
+            }
         }
         if args.len() >= 255 {
-            Err(ParseError::from((
-                self.current().clone(),
-                "Can't have more than 255 arguments.",
-            )))
+            Err(ParseError::TooMany {
+                found: self.current().clone(),
+                what: "arguments",
+            })
         } else {
             Ok(args)
         }
     }
 
+    // Skips the rest of a malformed argument, stopping before the ',' or ')'
+    // that ends it. Returns false if we hit the end of the statement first.
+    fn skip_argument(&mut self) -> bool {
+        let mut nesting = 0;
+        loop {
+            match self.peek() {
+                Comma | RightParen if nesting == 0 => return true,
+                LeftParen => nesting += 1,
+                RightParen => nesting -= 1,
+                Semicolon | LeftBrace | RightBrace | Eof => return false,
+                _ => {}
+            }
+            self.advance();
+        }
+    }
+
     fn call(&mut self) -> ParseExpr {
         let mut expr = self.primary()?;
         //while self.token_match(&[LeftParen]) {
@@ -466,7 +660,7 @@ impl<'long> Parser<'long> {
                 continue;
             }
             if self.token_match(&[Dot]) {
-                self.consume(Identifier, "Expected identifier in property access")?;
+                self.consume(Identifier, "for property name after '.'")?;
                 let name = self.previous();
                 expr = Expr::Get {
                     object: Box::new(expr),
@@ -482,61 +676,161 @@ impl<'long> Parser<'long> {
     fn primary(&mut self) -> ParseExpr {
         let cur_token = self.peek();
         match cur_token {
-            Eof => Err(ParseError::UnexpectedEof(self.current)),
             False | True | Nil | TNumber(_) | TString(_) => {
-                let expr = Expr::Literal(cur_token.into());
+                let value = cur_token.into();
                 self.advance();
-                Ok(expr)
+                Ok(Expr::Literal(value, Some(self.previous())))
             }
             This => {
                 self.advance();
                 Ok(Expr::This(self.previous()))
             }
+            InterpolationStart(_) => self.interpolation(),
             Super => {
                 self.advance();
                 let this = self.previous();
-                self.consume(Dot, "Expect '.' after super")?;
-                self.consume(Identifier, "Expecte identifier for super property access")?;
+                self.consume(Dot, "after 'super'")?;
+                self.consume(Identifier, "for superclass method name")?;
                 let property = self.previous();
                 Ok(Expr::Super(this, property))
             }
 
+            LeftParen if self.arrow_ahead() => {
+                self.advance();
+                let line = self.current_line();
+                let (parameters, parameter_types) = self.parameters()?;
+                let return_type = self.annotation()?;
+                self.consume(Arrow, "after parameters")?;
+                let name = lambda_name(self.previous());
+                let body_line = self.current().line;
+                let body = Stmt::Return(body_line, self.expression()?);
+                Ok(Expr::Lambda {
+                    fun_decl: FunDecl {
+                        line,
+                        name,
+                        parameters,
+                        parameter_types,
+                        return_type,
+                        body: StmtList {
+                            stmts: vec![body],
+                            end_line: self.current_line(),
+                            from_for: false,
+                        }
+                        .into(),
+                        is_static: false,
+                        is_getter: false,
+                    }
+                    .into(),
+                    arrow: true,
+                })
+            }
             LeftParen => {
                 self.advance();
                 let expr = self.expression()?;
-                self.consume(RightParen, "Expect ')' after expression")?;
+                self.consume(RightParen, "after expression")?;
                 Ok(Expr::Grouping(Box::new(expr)))
             }
             Identifier => {
                 self.advance();
                 Ok(Expr::Variable(self.previous()))
             }
-            _ => {
-                let token = self.tokens[self.current].clone();
-                let err_msg = "unexpected token";
-                Err(ParseError::from((token, err_msg)))
+            Fun => {
+                self.advance();
+                let name = lambda_name(self.previous());
+                let line = self.current_line();
+                self.consume(LeftParen, "after 'fun'")?;
+                Ok(Expr::Lambda {
+                    fun_decl: self.function(name, line)?.into(),
+                    arrow: false,
+                })
+            }
+            _ => Err(ParseError::ExpectedExpression {
+                found: self.current().clone(),
+            }),
+        }
+    }
+
+    // `"a ${b} c ${d}"` is scanned as InterpolationStart("a "), the tokens
+    // of b, InterpolationMid(" c "), the tokens of d, InterpolationEnd("").
+    fn interpolation(&mut self) -> ParseExpr {
+        let mut parts = vec![];
+        loop {
+            match self.peek().clone() {
+                InterpolationStart(text) | InterpolationMid(text) => {
+                    self.advance();
+                    parts.push(Expr::Literal(Value::VString(text), None));
+                    parts.push(self.expression()?);
+                }
+                InterpolationEnd(text) => {
+                    self.advance();
+                    parts.push(Expr::Literal(Value::VString(text), None));
+                    return Ok(Expr::Interpolation(parts));
+                }
+                _ => return Err(self.expected(&[RightBrace], "after interpolated expression")),
             }
         }
     }
 
+    // Whether the '(' we're at opens the parameters of an arrow function,
+    // which takes looking past them for the '=>'.
+    fn arrow_ahead(&self) -> bool {
+        let mut rest = self.tokens[self.current + 1..].iter().map(|t| &t.token);
+        // parameter names, commas and annotations
+        if rest.find(|t| !matches!(t, Identifier | Comma | Colon | Nil)) != Some(&RightParen) {
+            return false;
+        }
+        match rest.next() {
+            Some(Arrow) => true,
+            // a return type
+            Some(Colon) => {
+                matches!(rest.next(), Some(Identifier | Nil)) && rest.next() == Some(&Arrow)
+            }
+            _ => false,
+        }
+    }
+
+    // Skips to the start of the next statement. Braced groups are skipped
+    // whole, and the '}' closing the block we are in is left for block().
     fn synchronize(&mut self) {
-        self.advance();
+        if self.depth > 0 && self.check(&RightBrace) {
+            return;
+        }
+        let mut nesting = 0;
         while !self.is_at_end() {
+            match self.peek() {
+                LeftBrace => nesting += 1,
+                RightBrace if nesting > 0 => nesting -= 1,
+                _ => {}
+            }
+            self.advance();
+            if nesting > 0 {
+                continue;
+            }
             if self.tokens[self.current - 1].token == Semicolon {
                 return;
             }
             match self.peek() {
-                Class | Fun | Var | For | If | While | Print | Return => return,
-                _ => self.advance(),
+                Class | Trait | Fun | Var | For | If | While | Print | Return => return,
+                RightBrace if self.depth > 0 => return,
+                _ => {}
             }
         }
     }
 }
 
+// Anonymous functions still need a name for stack traces and for printing
+// them, which they take along with the position of the token given.
+fn lambda_name(token: Token) -> Token {
+    Token {
+        lexeme: "lambda".into(),
+        ..token
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::Scanner;
+    use crate::scanner::Scanner;
 
     //#[rstest::rstest]
     //fn test_parse_expr(#[case] input: &str, #[case] want: &str) -> Result<(), LoxError> {
@@ -566,6 +860,7 @@ mod tests {
     #[case("print \"hello, world\";", "print(hello, world)")]
     #[case("var x = 17 + 1;", "var(x = (+ 17 1))")]
     #[case("var y;", "var(y)")]
+    #[case("var z: num = 1;", "var(z = 1)")]
     fn test_parse_stmt(#[case] input: &str, #[case] want: &str) -> Result<(), LoxError> {
         let mut scanner = Scanner::new(input);
         let tokens = scanner.scan_tokens()?;
@@ -611,10 +906,34 @@ expr((= v#i (+ v#i 1)))
     #[case("f();", "expr((v#f))\n")]
     #[case("fun f() {}", "(defn f '() {})\n")]
     #[case("fun f(a, b) { a + b; }", "(defn f '(a b) {expr((+ v#a v#b)) })\n")]
+    #[case("fun f(a: num, b): nil {}", "(defn f '(a b) {})\n")]
+    #[case(
+        "var f = fun (a) { print a; };",
+        "var(f = (defn lambda '(a) {print(v#a) }))\n"
+    )]
+    #[case("fun () {}();", "expr(((defn lambda '() {})))\n")]
+    #[case(
+        "f((a, b) => a + b);",
+        "expr((v#f (defn lambda '(a b) {(return (+ v#a v#b)) })))\n"
+    )]
+    #[case("(): num => 1;", "expr((defn lambda '() {(return 1) }))\n")]
+    #[case("(a);", "expr((group v#a))\n")]
+    #[case(
+        "class M { class sq(n) { return n * n; } pi { return 3; } }",
+        "(defclass M (static (defn sq '(n) {(return (* v#n v#n)) })) (defget pi {(return 3) }) )\n"
+    )]
     #[case(
         "class X { f() {} g(a, b) { print a + b; } }",
         "(defclass X (defn f '() {}) (defn g '(a b) {print((+ v#a v#b)) }) )\n"
     )]
+    #[case(
+        r#"print "a ${x + 1}${"b"} c";"#,
+        "print((interp \"a \" (+ v#x 1) \"\" \"b\" \" c\"))\n"
+    )]
+    #[case(
+        "trait T { f() {} } class A < B with T, U {}",
+        "(deftrait T (defn f '() {}) )\n(defclass A (from v#B) (with v#T v#U) )\n"
+    )]
     fn test_parse(#[case] input: &str, #[case] want: &str) -> Result<(), LoxError> {
         let mut scanner = Scanner::new(input);
         let tokens = scanner.scan_tokens()?;
@@ -628,31 +947,57 @@ expr((= v#i (+ v#i 1)))
     #[rstest::rstest]
     #[case(
         "( \"partial\" + \"group\" ;",
-        "[line 1] Error at ';': Expect ')' after expression"
+        "[line 1] Error at ';': expected ')' after expression"
+    )]
+    #[case("2 +", "[line 1] Error at end: expected expression")]
+    #[case("+ 1", "[line 1] Error at '+': expected expression")]
+    #[case("2 + ;", "[line 1] Error at ';': expected expression")]
+    #[case("print 4\n2 + 4", "[line 2] Error at '2': expected ';' after value")]
+    #[case(
+        "print 4;\n 2 + 4",
+        "[line 2] Error at end: expected ';' after expression"
     )]
-    #[case("2 +", "[line 2] Error: unexpected eof")]
-    #[case("+ 1", "[line 1] Error at '+': unexpected token")]
-    #[case("2 + ;", "[line 1] Error at ';': unexpected token")]
-    #[case("print 4\n2 + 4", "[line 2] Error at '2': Expect ';' after value.")]
-    #[case("print 4;\n 2 + 4", "[line 2] Error at end: Expect ';' after value.")]
     #[case(
         "var 72;",
-        "[line 1] Error at '72': expected identifier in declaration"
+        "[line 1] Error at '72': expected identifier for variable name"
     )]
     #[case(
         "var 72 = 4;",
-        "[line 1] Error at '72': expected identifier in declaration"
+        "[line 1] Error at '72': expected identifier for variable name"
     )]
     #[case(
         "var ident + 2 = \"value\";",
-        "[line 1] Error at '+': Expected ';' after variable declaration"
+        "[line 1] Error at '+': expected ';' after variable declaration"
     )]
     #[case(
         "var y",
-        "[line 1] Error at end: Expected ';' after variable declaration"
+        "[line 1] Error at end: expected ';' after variable declaration"
+    )]
+    #[case("17 = a", "[line 1] Error at '=': invalid assignment target")]
+    #[case("a = 17 = b", "[line 1] Error at '=': invalid assignment target")]
+    #[case("f(a b);", "[line 1] Error at 'b': expected ',' or ')' after argument")]
+    #[case(
+        "fun f(a;",
+        "[line 1] Error at ';': expected ',' or ')' after parameter"
+    )]
+    #[case(
+        "var x: 1;",
+        "[line 1] Error at '1': expected identifier for type name"
+    )]
+    #[case(
+        "fun {}",
+        "[line 1] Error at '{': expected identifier for function name"
+    )]
+    #[case(
+        "class A { b }",
+        "[line 1] Error at '}': expected '(' or '{' after method name"
+    )]
+    #[case("(a, 1) => a;", "[line 1] Error at ',': expected ')' after expression")]
+    #[case(
+        r#"print "a ${b c}";"#,
+        "[line 1] Error at 'c': expected '}' after interpolated expression"
     )]
-    #[case("17 = a", "[line 1] Error at '=': Invalid assignment target.")]
-    #[case("a = 17 = b", "[line 1] Error at '=': Invalid assignment target.")]
+    #[case("print \"a\n${1 +}\";", "[line 2] Error at '}\"': expected expression")]
     fn test_parse_errors(#[case] input: &str, #[case] want: &str) -> Result<(), LoxError> {
         let mut scanner = Scanner::new(input);
         let tokens = scanner.scan_tokens()?;
@@ -661,4 +1006,112 @@ expr((= v#i (+ v#i 1)))
         assert_eq!(format!("{}", got), want);
         Ok(())
     }
+
+    #[test]
+    fn test_parse_error_fields() -> Result<(), LoxError> {
+        let mut scanner = Scanner::new("while (x {}");
+        let tokens = scanner.scan_tokens()?;
+        let mut parser = Parser::new(&tokens);
+        let got = parser.parse().expect_err("should have failed to parse");
+        assert_eq!(
+            got,
+            LoxError::ParseErrors(vec![ParseError::Expected {
+                expected: vec![RightParen],
+                found: tokens[3].clone(),
+                context: "after condition",
+            }])
+        );
+        Ok(())
+    }
+
+    // Each script is broken in more than one place; every break should be
+    // reported once, without cascading errors from the recovery.
+    #[rstest::rstest]
+    #[case(
+        include_str!("../testdata/fib_slow.lox")
+            .replace("(n <= 1)", "(n <= )")
+            .replace("fib(n - 2) +", "fib(n - 2 +")
+            .replace("i = i + 1", "i = i +"),
+        "[line 2] Error at ')': expected expression
+[line 3] Error at ';': expected ',' or ')' after argument
+[line 6] Error at ')': expected expression"
+    )]
+    #[case(
+        include_str!("../testdata/fib_timer.lox")
+            .replace("fib(n - 1) + fib(n - 2)", "fib(n - 1,, n) + fib(n - 2")
+            .replace("var after", "var 4"),
+        "[line 3] Error at ',': expected expression
+[line 3] Error at ';': expected ',' or ')' after argument
+[line 8] Error at '4': expected identifier for variable name"
+    )]
+    #[case(
+        include_str!("../testdata/scope.lox")
+            .replace("var a = \"local\";", "var a = \"local\"")
+            .replace("scope(\"word\")", "scope(\"word\" x)"),
+        "[line 3] Error at 'print': expected ';' after variable declaration
+[line 5] Error at 'x': expected ',' or ')' after argument"
+    )]
+    #[case(
+        include_str!("../testdata/fib.lox")
+            .replace("a < 100000;", "a < 100000")
+            .replace("print a;", "print { a };"),
+        "[line 4] Error at 'b': expected ';' after loop condition"
+    )]
+    #[case(
+        include_str!("../testdata/fib.lox").replace("print a;", "print { a };"),
+        "[line 5] Error at '{': expected expression"
+    )]
+    fn test_parse_recovery(#[case] input: String, #[case] want: &str) -> Result<(), LoxError> {
+        let mut scanner = Scanner::new(&input);
+        let tokens = scanner.scan_tokens()?;
+        let mut parser = Parser::new(&tokens);
+        let got = parser.parse().expect_err("should have failed to parse");
+        assert_eq!(format!("{}", got), want);
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case(
+        "var a = 1;\nprint a +;\nprint a;",
+        "var(a = 1)\nerror\nprint(v#a)\n",
+        1
+    )]
+    #[case(
+        "fun f() {\n  var x = ;\n  return x;\n}\nf(;",
+        "(defn f '() {error (return v#x) })\nerror\n",
+        2
+    )]
+    #[case("print f(1 +, 2);\nprint f(1, 2);", "error\nprint((v#f 1 2))\n", 1)]
+    #[case("if (f(1 +)) {\n  print g(1);\n}\nprint 3;", "error\nprint(3)\n", 1)]
+    #[case("{ print 1 }\nprint 2;", "{\nerror\n}\nprint(2)\n", 1)]
+    fn test_parse_partial(
+        #[case] input: &str,
+        #[case] want: &str,
+        #[case] want_errors: usize,
+    ) -> Result<(), LoxError> {
+        let mut scanner = Scanner::new(input);
+        let tokens = scanner.scan_tokens()?;
+        let mut parser = Parser::new(&tokens);
+        let (stmts, errors) = parser.parse_partial();
+        assert_eq!(format!("{}", stmts), want);
+        assert_eq!(errors.len(), want_errors);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_error_limit() -> Result<(), LoxError> {
+        let input = "print ;\n".repeat(MAX_ERRORS + 5);
+        let mut scanner = Scanner::new(&input);
+        let tokens = scanner.scan_tokens()?;
+        let mut parser = Parser::new(&tokens);
+        let LoxError::ParseErrors(errors) = parser.parse().expect_err("should fail") else {
+            panic!("expected parse errors");
+        };
+        assert_eq!(errors.len(), MAX_ERRORS + 1);
+        assert_eq!(
+            errors.last(),
+            Some(&ParseError::TooManyErrors(MAX_ERRORS + 1))
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,295 @@
+use crate::error::RuntimeError;
+use crate::interpreter::Hook;
+use crate::interpreter::Interpreter;
+use crate::models::Stmt;
+use compact_str::CompactString;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::time::Duration;
+use std::time::Instant;
+
+const HOT_LINES: usize = 10;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FunctionStats {
+    pub calls: usize,
+    // time from entering to leaving, including callees
+    pub inclusive: Duration,
+    // inclusive time minus time spent in callees
+    pub exclusive: Duration,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LineStats {
+    // statements started on the line
+    pub hits: usize,
+    // time spent on the line, not counting calls made from it
+    pub time: Duration,
+}
+
+// A frame the profiler is timing.
+#[derive(Debug)]
+struct Active {
+    entered: Instant,
+    callees: Duration,
+}
+
+// Times functions and lines through the interpreter's hook. Functions are
+// keyed by name, with the top level counted as `script`. Call `finish` once
+// the run is over to account for the script frame.
+#[derive(Debug, Default)]
+pub struct Profiler {
+    pub functions: HashMap<CompactString, FunctionStats>,
+    pub lines: HashMap<usize, LineStats>,
+    // exclusive time per call stack, e.g. "script;fib;fib"
+    pub stacks: HashMap<String, Duration>,
+    active: Vec<Active>,
+    // the line being timed and since when
+    current: Option<(usize, Instant)>,
+}
+
+impl Profiler {
+    // Closes the running line's time slice and starts timing another.
+    fn switch_line(&mut self, now: Instant, line: Option<usize>) {
+        if let Some((current, since)) = self.current {
+            self.lines.entry(current).or_default().time += now - since;
+        }
+        self.current = line.map(|line| (line, now));
+    }
+
+    fn start(&mut self, now: Instant) {
+        if self.active.is_empty() {
+            self.active.push(Active {
+                entered: now,
+                callees: Duration::ZERO,
+            });
+        }
+    }
+
+    fn stop_frame(&mut self, now: Instant, stack: &[&str]) {
+        let Some(active) = self.active.pop() else {
+            return;
+        };
+        let inclusive = now - active.entered;
+        let exclusive = inclusive.saturating_sub(active.callees);
+        if let Some(caller) = self.active.last_mut() {
+            caller.callees += inclusive;
+        }
+        let (name, callers) = stack.split_last().unwrap_or((&"script", &[]));
+        let stats = self.functions.entry((*name).into()).or_default();
+        stats.calls += 1;
+        // a recursive call's time is already inside its outermost call's
+        if !callers.contains(name) {
+            stats.inclusive += inclusive;
+        }
+        stats.exclusive += exclusive;
+        *self.stacks.entry(stack.join(";")).or_default() += exclusive;
+    }
+
+    pub fn finish(&mut self) {
+        let now = Instant::now();
+        self.switch_line(now, None);
+        // calls leave even when they fail, so only the script is still running
+        self.stop_frame(now, &["script"]);
+    }
+
+    // Functions by exclusive time, then the hottest lines.
+    pub fn report(&self) -> String {
+        let mut out = String::new();
+        let mut functions: Vec<_> = self.functions.iter().collect();
+        functions.sort_by(|a, b| b.1.exclusive.cmp(&a.1.exclusive).then(a.0.cmp(b.0)));
+        writeln!(
+            out,
+            "{:<20} {:>10} {:>14} {:>14}",
+            "function", "calls", "inclusive ms", "exclusive ms"
+        )
+        .expect("must be able to append to string");
+        for (name, stats) in functions {
+            writeln!(
+                out,
+                "{:<20} {:>10} {:>14.3} {:>14.3}",
+                name,
+                stats.calls,
+                millis(stats.inclusive),
+                millis(stats.exclusive)
+            )
+            .expect("must be able to append to string");
+        }
+
+        let mut lines: Vec<_> = self.lines.iter().collect();
+        lines.sort_by(|a, b| b.1.time.cmp(&a.1.time).then(a.0.cmp(b.0)));
+        writeln!(out, "\n{:<20} {:>10} {:>14}", "line", "hits", "time ms")
+            .expect("must be able to append to string");
+        for (line, stats) in lines.into_iter().take(HOT_LINES) {
+            writeln!(
+                out,
+                "{:<20} {:>10} {:>14.3}",
+                line,
+                stats.hits,
+                millis(stats.time)
+            )
+            .expect("must be able to append to string");
+        }
+        out
+    }
+
+    // One "stack microseconds" line per call stack, as read by flamegraph.pl
+    // and inferno.
+    pub fn folded(&self) -> String {
+        let mut stacks: Vec<_> = self.stacks.iter().collect();
+        stacks.sort();
+        let mut out = String::new();
+        for (stack, time) in stacks {
+            writeln!(out, "{stack} {}", time.as_micros())
+                .expect("must be able to append to string");
+        }
+        out
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+fn stack(interpreter: &Interpreter) -> Vec<&str> {
+    interpreter
+        .frames
+        .iter()
+        .map(|frame| frame.function.as_str())
+        .collect()
+}
+
+impl Hook for Profiler {
+    fn statement(
+        &mut self,
+        _interpreter: &mut Interpreter,
+        line: usize,
+        _stmt: &Stmt,
+    ) -> Result<(), RuntimeError> {
+        let now = Instant::now();
+        self.start(now);
+        self.switch_line(now, Some(line));
+        self.lines.entry(line).or_default().hits += 1;
+        Ok(())
+    }
+
+    fn enter(&mut self, interpreter: &Interpreter) {
+        let now = Instant::now();
+        self.start(now);
+        let line = interpreter.frames.last().map(|frame| frame.line);
+        self.switch_line(now, line);
+        self.active.push(Active {
+            entered: now,
+            callees: Duration::ZERO,
+        });
+    }
+
+    fn leave(&mut self, interpreter: &Interpreter) {
+        let now = Instant::now();
+        let frames = &interpreter.frames;
+        // back to timing the line the call was made from
+        let caller = frames.len().checked_sub(2).map(|i| frames[i].line);
+        self.switch_line(now, caller);
+        self.stop_frame(now, &stack(interpreter));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::LoxResult;
+    use crate::parser::Parser;
+    use crate::resolver::Resolver;
+    use crate::scanner::Scanner;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn profile(input: &str) -> LoxResult<Profiler> {
+        let profiler = Rc::new(RefCell::new(Profiler::default()));
+        let mut interpreter = Interpreter {
+            hook: Some(Box::new(profiler.clone())),
+            ..Default::default()
+        };
+        let mut scanner = Scanner::new(input);
+        let tokens = scanner.scan_tokens()?;
+        let mut parser = Parser::new(&tokens);
+        let stmts = parser.parse()?;
+        let mut resolver = Resolver::default();
+        interpreter.resolutions = resolver.resolve(&stmts)?;
+        interpreter.interpret(&stmts)?;
+        drop(interpreter);
+        let mut profiler = Rc::into_inner(profiler)
+            .expect("interpreter is gone")
+            .into_inner();
+        profiler.finish();
+        Ok(profiler)
+    }
+
+    #[test]
+    fn test_profile_counts() -> LoxResult<()> {
+        let input = r#"fun fib(n) {
+  if (n < 2) return n;
+  return fib(n - 1) + fib(n - 2);
+}
+fun main() {
+  print fib(5);
+}
+main();
+main();
+"#;
+        let profiler = profile(input)?;
+        let calls = |name: &str| profiler.functions[name].calls;
+        assert_eq!(calls("script"), 1);
+        assert_eq!(calls("main"), 2);
+        assert_eq!(calls("fib"), 30);
+
+        let hits = |line| profiler.lines[&line].hits;
+        // the if and, for the 16 base cases, its return
+        assert_eq!(hits(2), 46);
+        assert_eq!(hits(3), 14);
+        assert_eq!(hits(6), 2);
+        assert_eq!(hits(8), 1);
+
+        let mut stacks: Vec<_> = profiler.stacks.keys().cloned().collect();
+        stacks.sort();
+        assert_eq!(
+            stacks,
+            vec![
+                "script",
+                "script;main",
+                "script;main;fib",
+                "script;main;fib;fib",
+                "script;main;fib;fib;fib",
+                "script;main;fib;fib;fib;fib",
+                "script;main;fib;fib;fib;fib;fib",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_profile_times() -> LoxResult<()> {
+        let input = "fun f(n) {\n  while (n > 0) n = n - 1;\n}\nf(100);\nf(100);\n";
+        let profiler = profile(input)?;
+        let f = &profiler.functions["f"];
+        let script = &profiler.functions["script"];
+        assert!(f.exclusive <= f.inclusive);
+        assert!(f.inclusive <= script.inclusive);
+        assert_eq!(script.inclusive, script.exclusive + f.inclusive);
+
+        let folded = profiler.folded();
+        let lines: Vec<_> = folded.lines().map(|l| l.rsplit_once(' ')).collect();
+        assert!(matches!(
+            lines[..],
+            [Some(("script", _)), Some(("script;f", _))]
+        ));
+        assert!(profiler.report().starts_with("function "));
+
+        let input = "fun f(n) {\n  if (n > 0) f(n - 1);\n}\nf(20);\n";
+        let profiler = profile(input)?;
+        let f = &profiler.functions["f"];
+        assert_eq!(f.calls, 21);
+        assert!(f.inclusive <= profiler.functions["script"].inclusive);
+        Ok(())
+    }
+}
@@ -3,6 +3,8 @@ use crate::models::FunDecl;
 use crate::models::Stmt;
 use crate::models::StmtList;
 use crate::models::Value;
+use crate::symbols::DeclKind;
+use crate::symbols::Symbols;
 use std::collections::HashMap;
 
 use crate::models::Token;
@@ -12,19 +14,46 @@ use thiserror::Error;
 
 #[derive(PartialEq, Debug, Error)]
 pub enum ResolverError {
-    #[error("variable accessed before definition: {0}")]
+    #[error("[line {}] Error at '{}': variable accessed before definition", .0.line, .0.lexeme)]
     AccessBeforeInit(Token),
-    #[error("variable redefined: {0}")]
+
+    #[error("[line {}] Error at '{}': variable redefined", .0.line, .0.lexeme)]
     AlreadyDefined(Token),
 
-    #[error("return outside of function: {0}")]
-    NoFuncReturn(String),
+    #[error("[line {0}] Error at 'return': return outside of function")]
+    NoFuncReturn(usize, String),
+
+    #[error("[line {0}] Error at 'return': can't return a value from an initializer")]
+    InitializerReturn(usize, String),
 
-    #[error("this outside of class: {0}")]
+    #[error("[line {}] Error at 'this': this outside of class", .0.line)]
     NoClassThis(Token),
 
-    #[error("super outside of subclass")]
-    NoSubclassSuper,
+    #[error("[line {}] Error at 'super': super outside of class", .0.line)]
+    NoClassSuper(Token),
+
+    #[error("[line {}] Error at 'super': super in a class without a superclass", .0.line)]
+    NoSubclassSuper(Token),
+
+    #[error("[line {}] Error at '{}': class can't inherit from itself", .0.line, .0.lexeme)]
+    InheritFromSelf(Token),
+
+    #[error("[line {}] Error at 'super': super in a trait", .0.line)]
+    TraitSuper(Token),
+
+    #[error("[line {}] Error at '{}': traits can't have class methods", .0.line, .0.lexeme)]
+    TraitClassMethod(Token),
+
+    #[error("[line {}] Error at 'init': an initializer can't be a getter or class method", .0.line)]
+    InitNotMethod(Token),
+
+    #[error("[line {}] Error at '{}': method '{method}' comes from both '{first}' and '{second}'", class.line, class.lexeme)]
+    TraitConflict {
+        class: Token,
+        method: CompactString,
+        first: CompactString,
+        second: CompactString,
+    },
 }
 
 #[derive(Debug, Clone, Copy, Default, PartialEq)]
@@ -42,6 +71,7 @@ enum ClassType {
     None,
     Class,
     Subclass,
+    Trait,
 }
 
 #[derive(Debug, Default)]
@@ -51,6 +81,12 @@ pub struct Resolver {
     scopes: Vec<HashMap<CompactString, bool>>,
     func_type: FuncType,
     class_type: ClassType,
+    // the methods of each trait declared so far, to find conflicts between
+    // the traits of a class
+    traits: HashMap<CompactString, Vec<CompactString>>,
+    symbols: Symbols,
+    // the symbol table scope matching each entry of scopes
+    symbol_scopes: Vec<usize>,
 }
 
 impl Resolver {
@@ -59,6 +95,7 @@ impl Resolver {
         stmt_list: &StmtList,
     ) -> Result<HashMap<*const Expr, usize>, Vec<ResolverError>> {
         self.resolve_stmts(stmt_list);
+        self.symbols.link_globals();
         if self.errors.is_empty() {
             Ok(mem::take(&mut self.resolutions))
         } else {
@@ -66,15 +103,27 @@ impl Resolver {
         }
     }
 
+    pub fn into_symbols(self) -> Symbols {
+        self.symbols
+    }
+
     fn begin_scope(&mut self) {
         self.scopes.push(Default::default());
+        let id = self.symbols.push_scope(self.symbol_scope());
+        self.symbol_scopes.push(id);
     }
 
     fn end_scope(&mut self) {
         self.scopes.pop();
+        self.symbol_scopes.pop();
+    }
+
+    fn symbol_scope(&self) -> usize {
+        self.symbol_scopes.last().copied().unwrap_or(0)
     }
 
-    fn declare(&mut self, token: &Token) {
+    fn declare(&mut self, token: &Token, kind: DeclKind) {
+        self.symbols.declare(self.symbol_scope(), token, kind);
         if let Some(scope) = self.scopes.last_mut() {
             let name = token.lexeme.clone();
             match scope.get(&name) {
@@ -114,25 +163,31 @@ impl Resolver {
     }
 
     fn resolve_stmts(&mut self, stmts: &StmtList) {
-        for stmt in stmts.0.iter() {
+        for stmt in stmts {
             self.resolve_stmt(stmt);
         }
     }
 
     fn resolve_function(&mut self, func_type: FuncType, fun_decl: &FunDecl) {
+        let kind = match func_type {
+            FuncType::Method | FuncType::Initializer => DeclKind::Method,
+            _ => DeclKind::Function,
+        };
+        self.declare(&fun_decl.name, kind);
+        self.define(&fun_decl.name);
+        self.resolve_body(func_type, fun_decl);
+    }
+
+    // Anonymous functions declare no name, so they go straight to this.
+    fn resolve_body(&mut self, func_type: FuncType, fun_decl: &FunDecl) {
         let FunDecl {
-            name,
-            parameters,
-            body,
-            ..
+            parameters, body, ..
         } = fun_decl;
         let enclosing_function = self.func_type;
         self.func_type = func_type;
-        self.declare(name);
-        self.define(name);
         self.begin_scope();
         for parameter in parameters {
-            self.declare(parameter);
+            self.declare(parameter, DeclKind::Parameter);
             self.define(parameter);
         }
         self.resolve_stmts(body);
@@ -140,12 +195,64 @@ impl Resolver {
         self.func_type = enclosing_function;
     }
 
+    // The methods of a class or trait, which can all use `this`.
+    fn resolve_methods(&mut self, methods: &[FunDecl]) {
+        self.begin_scope();
+        self.scopes.last_mut().unwrap().insert("this".into(), true);
+        for method in methods {
+            let func_type = match method.name.lexeme == "init" {
+                // calling the class would run it
+                true if method.is_static || method.is_getter => {
+                    self.errors
+                        .push(ResolverError::InitNotMethod(method.name.clone()));
+                    FuncType::Method
+                }
+                true => FuncType::Initializer,
+                false => FuncType::Method,
+            };
+            self.resolve_function(func_type, method);
+        }
+        self.end_scope();
+    }
+
+    // A method may come from only one of a class's traits, unless the class
+    // defines it itself. Traits this hasn't seen declared, e.g. ones reached
+    // through another variable, are checked by the interpreter when the
+    // class is declared.
+    fn check_traits(&mut self, class: &Token, traits: &[Expr], methods: &[FunDecl]) {
+        let mut seen: HashMap<&CompactString, &CompactString> = HashMap::new();
+        for t in traits {
+            let Expr::Variable(t) = t else {
+                continue;
+            };
+            let Some(names) = self.traits.get(&t.lexeme) else {
+                continue;
+            };
+            for method in names {
+                if methods.iter().any(|m| m.name.lexeme == *method) {
+                    continue;
+                }
+                match seen.get(method) {
+                    Some(first) => self.errors.push(ResolverError::TraitConflict {
+                        class: class.clone(),
+                        method: method.clone(),
+                        first: (*first).clone(),
+                        second: t.lexeme.clone(),
+                    }),
+                    None => {
+                        seen.insert(method, &t.lexeme);
+                    }
+                }
+            }
+        }
+    }
+
     fn resolve_stmt(&mut self, stmt: &Stmt) {
         match stmt {
             Stmt::Expr(_, expr) => self.resolve_expr(expr),
             Stmt::Print(_, expr) => self.resolve_expr(expr),
-            Stmt::VarDecl(_, token, expr) => {
-                self.declare(token);
+            Stmt::VarDecl(_, token, _, expr) => {
+                self.declare(token, DeclKind::Variable);
                 if let Some(expr) = expr {
                     self.resolve_expr(expr);
                 }
@@ -156,17 +263,18 @@ impl Resolver {
                 name,
                 methods,
                 parent,
+                traits,
                 ..
             } => {
                 let enclosing_class = self.class_type;
                 self.class_type = ClassType::Class;
-                self.declare(name);
+                self.declare(name, DeclKind::Class);
                 self.define(name);
                 if let Some(p) = parent {
                     if let Expr::Variable(var) = p {
                         if name.lexeme == var.lexeme {
-                            // I should have used anyhow
-                            panic!("recursive class def");
+                            self.errors
+                                .push(ResolverError::InheritFromSelf(var.clone()));
                         }
                     } else {
                         panic!("non var parent class");
@@ -174,27 +282,34 @@ impl Resolver {
                     self.class_type = ClassType::Subclass;
                     self.resolve_expr(p);
                 }
+                for t in traits {
+                    self.resolve_expr(t);
+                }
+                self.check_traits(name, traits, methods);
                 if parent.is_some() {
                     self.begin_scope();
                     self.scopes.last_mut().unwrap().insert("super".into(), true);
                 }
-                self.begin_scope();
-                self.scopes.last_mut().unwrap().insert("this".into(), true);
-
-                for method in methods {
-                    let func_type = if method.name.lexeme == "init" {
-                        FuncType::Initializer
-                    } else {
-                        FuncType::Method
-                    };
-                    self.resolve_function(func_type, method);
-                }
-                self.end_scope();
+                self.resolve_methods(methods);
                 if parent.is_some() {
                     self.end_scope();
                 }
                 self.class_type = enclosing_class;
             }
+            Stmt::TraitDecl { name, methods, .. } => {
+                let enclosing_class = self.class_type;
+                self.class_type = ClassType::Trait;
+                self.declare(name, DeclKind::Trait);
+                self.define(name);
+                for method in methods.iter().filter(|method| method.is_static) {
+                    self.errors
+                        .push(ResolverError::TraitClassMethod(method.name.clone()));
+                }
+                let names = methods.iter().map(|m| m.name.lexeme.clone()).collect();
+                self.traits.insert(name.lexeme.clone(), names);
+                self.resolve_methods(methods);
+                self.class_type = enclosing_class;
+            }
             Stmt::Block(stmts) => {
                 self.begin_scope();
                 self.resolve_stmts(stmts);
@@ -216,38 +331,52 @@ impl Resolver {
                 self.resolve_expr(expr);
                 self.resolve_stmt(stmt);
             }
-            Stmt::Return(_, expr) => {
-                if !matches!(expr, Expr::Literal(Value::VNil))
-                    && matches!(self.func_type, FuncType::None | FuncType::Initializer)
-                {
-                    self.errors
-                        .push(ResolverError::NoFuncReturn(format!("{expr}")));
+            Stmt::Return(line, expr) => {
+                let returns_value = !matches!(expr, Expr::Literal(Value::VNil, _));
+                match self.func_type {
+                    FuncType::None => self
+                        .errors
+                        .push(ResolverError::NoFuncReturn(*line, format!("{expr}"))),
+                    FuncType::Initializer if returns_value => self
+                        .errors
+                        .push(ResolverError::InitializerReturn(*line, format!("{expr}"))),
+                    _ => {}
                 }
                 self.resolve_expr(expr);
             }
+            Stmt::Error(_) => {}
         }
     }
 
     fn resolve_expr(&mut self, expr: &Expr) {
         use Expr::*;
         match expr {
-            Literal(_) => {}
-            Variable(token) => self.resolve_local(expr, token),
+            Literal(..) => {}
+            Variable(token) => {
+                self.resolve_local(expr, token);
+                self.symbols.reference(self.symbol_scope(), token);
+            }
             This(token) => {
                 if self.class_type == ClassType::None {
                     self.errors.push(ResolverError::NoClassThis(token.clone()))
                 }
                 self.resolve_local(expr, token);
             }
-            Super(_, keyword) => {
-                if self.class_type != ClassType::Subclass {
-                    self.errors.push(ResolverError::NoSubclassSuper);
+            Super(token, _) => {
+                match self.class_type {
+                    ClassType::None => self.errors.push(ResolverError::NoClassSuper(token.clone())),
+                    ClassType::Class => self
+                        .errors
+                        .push(ResolverError::NoSubclassSuper(token.clone())),
+                    ClassType::Subclass => {}
+                    ClassType::Trait => self.errors.push(ResolverError::TraitSuper(token.clone())),
                 }
-                self.resolve_local(expr, keyword);
+                self.resolve_local(expr, token);
             }
             Assign { name, value } => {
                 self.resolve_expr(value);
-                self.resolve_local(expr, name)
+                self.resolve_local(expr, name);
+                self.symbols.reference(self.symbol_scope(), name);
             }
             Grouping(expr) => self.resolve_expr(expr),
             Unary { right, .. } => self.resolve_expr(right),
@@ -274,6 +403,12 @@ impl Resolver {
                 self.resolve_expr(value);
                 // we can't statically resolve fields because the language is dynamic
             }
+            Lambda { fun_decl, .. } => self.resolve_body(FuncType::Function, fun_decl),
+            Interpolation(parts) => {
+                for part in parts {
+                    self.resolve_expr(part);
+                }
+            }
         }
     }
 }
@@ -332,5 +467,78 @@ var a = "global";
         Ok(())
     }
 
-    // TODO: test resolver error cases
+    #[test]
+    fn test_resolve_partial() -> Result<(), LoxError> {
+        let input = "{ var a = 1; print a +; { print a; } }";
+        let mut scanner = Scanner::new(input);
+        let tokens = scanner.scan_tokens()?;
+        let mut parser = Parser::new(&tokens);
+        let (stmts, errors) = parser.parse_partial();
+        assert_eq!(errors.len(), 1);
+        let mut resolver = Resolver::default();
+        let resolutions = resolver.resolve(&stmts)?;
+        let got_depths: Vec<_> = resolutions.values().cloned().collect();
+        assert_eq!(got_depths, vec![1]);
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case(
+        "{ var a = a; }",
+        "[line 1] Error at 'a': variable accessed before definition"
+    )]
+    #[case("fun f(a) {\n  var a;\n}", "[line 2] Error at 'a': variable redefined")]
+    #[case("return;", "[line 1] Error at 'return': return outside of function")]
+    #[case(
+        "class A {\n  init() {\n    return 1;\n  }\n}",
+        "[line 3] Error at 'return': can't return a value from an initializer"
+    )]
+    #[case("class A { init() { return; } }", "")]
+    #[case("print this;", "[line 1] Error at 'this': this outside of class")]
+    #[case("super.f();", "[line 1] Error at 'super': super outside of class")]
+    #[case(
+        "class A { f() { super.f(); } }",
+        "[line 1] Error at 'super': super in a class without a superclass"
+    )]
+    #[case(
+        "class A < A {}",
+        "[line 1] Error at 'A': class can't inherit from itself"
+    )]
+    #[case(
+        "trait T { f() {} }\ntrait U { f() {} }\nclass A with T, U {}",
+        "[line 3] Error at 'A': method 'f' comes from both 'T' and 'U'"
+    )]
+    #[case(
+        "trait T { f() {} }\ntrait U { f() {} }\nclass A with T, U { f() {} }",
+        ""
+    )]
+    #[case(
+        "trait T { f() { super.f(); } }",
+        "[line 1] Error at 'super': super in a trait"
+    )]
+    #[case(
+        "trait T { class f() {} }",
+        "[line 1] Error at 'f': traits can't have class methods"
+    )]
+    #[case(
+        "class A {\n  init { return 1; }\n}",
+        "[line 2] Error at 'init': an initializer can't be a getter or class method"
+    )]
+    #[case(
+        "class A {\n  class init() {}\n}",
+        "[line 2] Error at 'init': an initializer can't be a getter or class method"
+    )]
+    fn test_resolver_errors(#[case] input: &str, #[case] want: &str) -> Result<(), LoxError> {
+        let mut scanner = Scanner::new(input);
+        let tokens = scanner.scan_tokens()?;
+        let mut parser = Parser::new(&tokens);
+        let stmts = parser.parse()?;
+        let mut resolver = Resolver::default();
+        let got = match resolver.resolve(&stmts) {
+            Ok(_) => String::new(),
+            Err(errors) => format!("{}", LoxError::from(errors)),
+        };
+        assert_eq!(got, want);
+        Ok(())
+    }
 }
@@ -1,5 +1,6 @@
 use crate::error::LoxError;
 use crate::error::ScanError;
+use crate::models::Comment;
 use crate::models::Token;
 use crate::models::TokenType;
 use crate::models::TokenType::*;
@@ -19,15 +20,18 @@ lazy_static! {
             ("for", For),
             ("fun", Fun),
             ("if", If),
+            ("is", Is),
             ("nil", Nil),
             ("or", Or),
             ("print", Print),
             ("return", Return),
             ("super", Super),
             ("this", This),
+            ("trait", Trait),
             ("true", True),
             ("var", Var),
             ("while", While),
+            ("with", With),
         ]
         .into_iter()
     );
@@ -37,11 +41,20 @@ pub struct Scanner<'a> {
     src: &'a str,
     chars: Peekable<CharIndices<'a>>,
     tokens: Vec<Token>,
+    comments: Vec<Comment>,
     errors: Vec<ScanError>,
 
     start: usize,
+    // byte offset just past the last consumed character
     current: usize,
     line: usize,
+    // chars consumed since the current line started
+    line_chars: usize,
+    // where the current token starts, as strings may run over several lines
+    start_line: usize,
+    column: usize,
+    // for each `${` we're inside, how many of its own braces are open
+    interpolations: Vec<usize>,
 }
 
 impl<'a> Scanner<'a> {
@@ -50,34 +63,59 @@ impl<'a> Scanner<'a> {
             src,
             chars: src.char_indices().peekable(),
             tokens: vec![],
+            comments: vec![],
             errors: vec![],
             start: 0,
             current: 0,
             line: 1,
+            line_chars: 0,
+            start_line: 1,
+            column: 1,
+            interpolations: vec![],
         }
     }
 
     pub fn scan_tokens(&mut self) -> Result<Vec<Token>, LoxError> {
-        while let Some((start, c)) = self.chars.peek() {
-            self.start = *start;
-            let c = *c;
+        let (tokens, errors) = self.scan_partial();
+        if errors.is_empty() {
+            Ok(tokens)
+        } else {
+            Err(errors)?
+        }
+    }
+
+    // Scans everything it can for tooling: bad characters and unterminated
+    // strings are reported but don't stop the rest of the file being tokenized.
+    pub fn scan_partial(&mut self) -> (Vec<Token>, Vec<ScanError>) {
+        while let Some(&(start, c)) = self.chars.peek() {
+            self.start = start;
+            self.start_line = self.line;
+            self.column = self.line_chars + 1;
             self.advance();
             self.scan_token(c);
         }
+        if !self.interpolations.is_empty() {
+            self.add_error("Unterminated interpolation.".to_owned());
+        }
         self.tokens.push(Token {
             token: Eof,
             lexeme: "".into(),
             line: self.line,
+            column: self.line_chars + 1,
         });
-        if self.errors.is_empty() {
-            Ok(mem::take(&mut self.tokens))
-        } else {
-            Err(mem::take(&mut self.errors))?
-        }
+        (mem::take(&mut self.tokens), mem::take(&mut self.errors))
+    }
+
+    fn newline(&mut self) {
+        self.line += 1;
+    }
+
+    pub fn comments(&self) -> &[Comment] {
+        &self.comments
     }
 
     fn buffered_str(&self) -> &'a str {
-        &self.src[self.start..self.current + 1]
+        &self.src[self.start..self.current]
     }
 
     fn is_at_end(&mut self) -> bool {
@@ -87,8 +125,9 @@ impl<'a> Scanner<'a> {
     fn advance(&mut self) {
         self.chars
             .next()
-            .map(|(cur, _)| {
-                self.current = cur;
+            .map(|(cur, c)| {
+                self.current = cur + c.len_utf8();
+                self.line_chars = if c == '\n' { 0 } else { self.line_chars + 1 };
             })
             .expect("I think we'll always have more? maybe not");
     }
@@ -97,21 +136,42 @@ impl<'a> Scanner<'a> {
         match c {
             '(' => self.add_token(LeftParen),
             ')' => self.add_token(RightParen),
-            '{' => self.add_token(LeftBrace),
-            '}' => self.add_token(RightBrace),
+            '{' => {
+                if let Some(depth) = self.interpolations.last_mut() {
+                    *depth += 1;
+                }
+                self.add_token(LeftBrace)
+            }
+            // the `}` closing an interpolation carries on with its string
+            '}' => match self.interpolations.last_mut() {
+                Some(0) => {
+                    self.interpolations.pop();
+                    self.string(false);
+                }
+                Some(depth) => {
+                    *depth -= 1;
+                    self.add_token(RightBrace)
+                }
+                None => self.add_token(RightBrace),
+            },
             ',' => self.add_token(Comma),
             '.' => self.add_token(Dot),
             '-' => self.add_token(Minus),
             '+' => self.add_token(Plus),
             ';' => self.add_token(Semicolon),
+            ':' => self.add_token(Colon),
             '*' => self.add_token(Star),
             '!' => {
                 let has_match = self.match_char('=');
                 self.add_token(if has_match { BangEqual } else { Bang });
             }
             '=' => {
-                let has_match = self.match_char('=');
-                self.add_token(if has_match { EqualEqual } else { Equal });
+                if self.match_char('>') {
+                    self.add_token(Arrow);
+                } else {
+                    let has_match = self.match_char('=');
+                    self.add_token(if has_match { EqualEqual } else { Equal });
+                }
             }
             '<' => {
                 let has_match = self.match_char('=');
@@ -127,14 +187,23 @@ impl<'a> Scanner<'a> {
                     while self.peek() != '\n' && !self.is_at_end() {
                         self.advance();
                     }
+                    self.comments.push(Comment {
+                        line: self.line,
+                        text: self.buffered_str().trim_end().into(),
+                    });
                 } else {
                     self.add_token(Slash);
                 }
             }
             ' ' | '\r' | '\t' => (),
-            '\n' => self.line += 1,
-            '"' => self.string(),
-            d if d.is_ascii_digit() => self.number(),
+            '\n' => self.newline(),
+            '"' if self.peek() == '"' && self.peek_next() == '"' => {
+                self.advance();
+                self.advance();
+                self.raw_string();
+            }
+            '"' => self.string(true),
+            d if d.is_ascii_digit() => self.number(d),
             d if d.is_ascii_alphabetic() || d == '_' => self.identifier(),
             _ => self.add_error(format!("Unexpected character: {c}")),
         }
@@ -157,8 +226,9 @@ impl<'a> Scanner<'a> {
     fn add_token(&mut self, token_type: TokenType) {
         self.tokens.push(Token {
             token: token_type,
-            line: self.line,
-            lexeme: (&self.src[self.start..self.current + 1]).into(),
+            line: self.start_line,
+            column: self.column,
+            lexeme: (&self.src[self.start..self.current]).into(),
         })
     }
 
@@ -177,48 +247,169 @@ impl<'a> Scanner<'a> {
     }
 
     fn peek_next(&mut self) -> char {
-        // We need this specifically to check for a digit, so
-        // ok to fail most code points.
-        let b = self.src.as_bytes()[self.current + 2];
-        char::from_u32(b as u32).unwrap_or('\0')
+        let mut chars = self.chars.clone();
+        chars.next();
+        chars.next().map_or('\0', |(_, c)| c)
     }
 
-    fn string(&mut self) {
-        while let Some((_, c)) = self.chars.peek()
-            && *c != '"'
-        {
-            if *c == '\n' {
-                self.line += 1
-            }
+    // Scans up to the closing quote, or up to a `${`, when the expression
+    // inside is scanned as usual until its `}` picks the string back up.
+    fn string(&mut self, opening: bool) {
+        let mut value = String::new();
+        while let Some(&(_, c)) = self.chars.peek() {
             self.advance();
+            match c {
+                '"' => {
+                    self.add_token(match opening {
+                        true => TString(value.into()),
+                        false => InterpolationEnd(value.into()),
+                    });
+                    return;
+                }
+                '\\' => self.escape(&mut value),
+                '$' if self.peek() == '{' => {
+                    self.advance();
+                    self.interpolations.push(0);
+                    self.add_token(match opening {
+                        true => InterpolationStart(value.into()),
+                        false => InterpolationMid(value.into()),
+                    });
+                    return;
+                }
+                '\n' => {
+                    self.newline();
+                    value.push(c);
+                }
+                c => value.push(c),
+            }
         }
-        if self.is_at_end() {
-            self.add_error("Unterminated string.".to_owned());
-            return;
-        }
+        self.add_error("Unterminated string.".to_owned());
+    }
+
+    // The character after a backslash; `\u{...}` takes up to six hex digits.
+    fn escape(&mut self, value: &mut String) {
+        let c = self.peek();
+        let escaped = match c {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '"' => '"',
+            '\\' => '\\',
+            '$' => '$',
+            'u' => {
+                self.advance();
+                return self.unicode_escape(value);
+            }
+            // left unconsumed, so a newline still counts as one
+            _ => {
+                self.add_error(format!("Invalid escape sequence: \\{c}"));
+                return;
+            }
+        };
         self.advance();
-        let value = self.buffered_str();
-        self.add_token(TString((&value[1..value.len() - 1]).into()));
+        value.push(escaped);
     }
 
-    fn number(&mut self) {
-        while self.peek().is_ascii_digit() {
+    fn unicode_escape(&mut self, value: &mut String) {
+        if !self.match_char('{') {
+            self.add_error("Invalid unicode escape: \\u".to_owned());
+            return;
+        }
+        let mut digits = String::new();
+        while self.peek().is_ascii_hexdigit() && digits.len() < 6 {
+            digits.push(self.peek());
             self.advance();
         }
-        if self.peek() == '.' && self.peek_next().is_ascii_digit() {
-            // Consume the "."
+        let c = u32::from_str_radix(&digits, 16)
+            .ok()
+            .and_then(char::from_u32);
+        match c {
+            Some(c) if self.match_char('}') => value.push(c),
+            _ => self.add_error(format!("Invalid unicode escape: \\u{{{digits}")),
+        }
+    }
+
+    // `"""` strings run to the next `"""`, across lines, with no escapes or
+    // interpolation.
+    fn raw_string(&mut self) {
+        while let Some(&(offset, c)) = self.chars.peek() {
+            if self.src[offset..].starts_with("\"\"\"") {
+                for _ in 0..3 {
+                    self.advance();
+                }
+                let value = &self.src[self.start + 3..offset];
+                self.add_token(TString(value.into()));
+                return;
+            }
+            if c == '\n' {
+                self.newline();
+            }
             self.advance();
-            while self.peek().is_ascii_digit() {
+        }
+        self.add_error("Unterminated string.".to_owned());
+    }
+
+    // Decimal numbers may have a fraction and an exponent; `0x` and `0b`
+    // start hex and binary integers. Any of them may use `_` between digits.
+    fn number(&mut self, first: char) {
+        let radix = match (first, self.peek()) {
+            ('0', 'x' | 'X') => 16,
+            ('0', 'b' | 'B') => 2,
+            _ => 10,
+        };
+        let mut ok = true;
+        if radix == 10 {
+            ok = self.digits(10);
+            // `5.x` is a property of 5, but `5.` on its own is unfinished
+            let next = self.peek_next();
+            if self.peek() == '.' && !(next.is_ascii_alphabetic() || next == '_') {
                 self.advance();
+                ok &= next.is_ascii_digit() && self.digits(10);
+            }
+            if self.match_char('e') || self.match_char('E') {
+                if !self.match_char('+') {
+                    self.match_char('-');
+                }
+                ok &= self.peek().is_ascii_digit() && self.digits(10);
             }
+        } else {
+            self.advance();
+            ok &= self.peek().is_digit(radix) && self.digits(radix);
+        }
+        // e.g. the 2 in 0b12, or letters run on from the digits
+        while self.peek().is_ascii_alphanumeric() || self.peek() == '_' {
+            self.advance();
+            ok = false;
+        }
+        if !ok {
+            self.add_error(format!("Malformed number: {}", self.buffered_str()));
+            return;
         }
-        let num: f64 = self
-            .buffered_str()
-            .parse()
-            .expect("this is already a number");
+        let text = self.buffered_str().replace('_', "");
+        let num = match radix {
+            10 => text.parse().expect("this is already a number"),
+            _ => text[2..].chars().fold(0.0, |n, d| {
+                n * radix as f64 + d.to_digit(radix).expect("already a digit") as f64
+            }),
+        };
         self.add_token(TNumber(num));
     }
 
+    // Consumes digits, which `_`s may separate, and says whether they were
+    // well formed: no `_` at the end or doubled.
+    fn digits(&mut self, radix: u32) -> bool {
+        let mut last = '0';
+        while self.peek().is_digit(radix) || self.peek() == '_' {
+            if last == '_' && self.peek() == '_' {
+                return false;
+            }
+            last = self.peek();
+            self.advance();
+        }
+        last != '_'
+    }
+
     fn identifier(&mut self) {
         while self.peek().is_ascii_alphanumeric() || self.peek() == '_' {
             self.advance();
@@ -250,18 +441,34 @@ mod tests {
             GreaterEqual, Less, LessEqual, Star, Eof
         ],
 vec![ "(", "!=", "!", "{", "-", ")", "+", "==", "}", "=", ";", "/", ">", ">=", "<", "<=", "*", ""], )]
-    #[case("and class else false for trap fun if nil or print return super this true var while",
+    #[case("and class else false for trap fun if is nil or print return super this trait true var while with",
         vec![
-        And, Class, Else, False, For, Identifier, Fun, If, Nil, Or, Print, Return,
-        Super, This, True, Var, While, Eof,
+        And, Class, Else, False, For, Identifier, Fun, If, Is, Nil, Or, Print, Return,
+        Super, This, Trait, True, Var, While, With, Eof,
     ], vec![
-        "and", "class", "else", "false", "for", "trap", "fun", "if", "nil",
-        "or", "print", "return", "super", "this", "true", "var", "while", "",
+        "and", "class", "else", "false", "for", "trap", "fun", "if", "is", "nil",
+        "or", "print", "return", "super", "this", "trait", "true", "var", "while", "with", "",
     ])]
     #[case("  var \t  x   = // a comment doesn't stop this\n 1894",
         vec![Var, Identifier, Equal, TNumber(1894.0), Eof],
         vec!["var", "x", "=", "1894", ""],
     )]
+    #[case("\"ünï\" 1.5.",
+        vec![TString("ünï".into()), TNumber(1.5), Dot, Eof],
+        vec!["\"ünï\"", "1.5", ".", ""],
+    )]
+    #[case("(a) => a == b",
+        vec![LeftParen, Identifier, RightParen, Arrow, Identifier, EqualEqual, Identifier, Eof],
+        vec!["(", "a", ")", "=>", "a", "==", "b", ""],
+    )]
+    #[case(r#""a ${ {x} } b${"c${d}"}\${e}""#,
+        vec![
+            InterpolationStart("a ".into()), LeftBrace, Identifier, RightBrace, InterpolationMid(" b".into()),
+            InterpolationStart("c".into()), Identifier, InterpolationEnd("".into()),
+            InterpolationEnd("${e}".into()), Eof,
+        ],
+        vec![r#""a ${"#, "{", "x", "}", "} b${", r#""c${"#, "d", r#"}""#, r#"}\${e}""#, ""],
+    )]
     fn test_scan_types(
         #[case] input: &str,
         #[case] want_types: Vec<TokenType>,
@@ -277,12 +484,113 @@ vec![ "(", "!=", "!", "{", "-", ")", "+", "==", "}", "=", ";", "/", ">", ">=", "
         Ok(())
     }
 
+    #[rstest::rstest]
+    #[case(r#""a\tb\n\"c\" \\ \$ \0""#, "a\tb\n\"c\" \\ $ \0")]
+    #[case(r#""\u{48}\u{e9}\u{1F600}""#, "Hé😀")]
+    #[case(
+        "\"\"\"raw \\n \"quotes\" ${x}\nline two\"\"\"",
+        "raw \\n \"quotes\" ${x}\nline two"
+    )]
+    #[case(r#""""""""#, "")]
+    fn test_scan_strings(#[case] input: &str, #[case] want: &str) -> Result<(), LoxError> {
+        let mut scanner = Scanner::new(input);
+        let tokens = scanner.scan_tokens()?;
+        assert_eq!(tokens[0].token, TString(want.into()));
+        assert_eq!(tokens[1].token, Eof);
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case("0", 0.0)]
+    #[case("0xFF", 255.0)]
+    #[case("0Xdead_BEEF", 3735928559.0)]
+    #[case("0b1010", 10.0)]
+    #[case("1_000_000", 1e6)]
+    #[case("1e-9", 1e-9)]
+    #[case("2.5E+3", 2500.0)]
+    #[case("1_0.2_5e1_0", 10.25e10)]
+    fn test_scan_numbers(#[case] input: &str, #[case] want: f64) -> Result<(), LoxError> {
+        let mut scanner = Scanner::new(input);
+        let tokens = scanner.scan_tokens()?;
+        assert_eq!(tokens[0].token, TNumber(want));
+        assert_eq!(tokens[0].lexeme, input);
+        assert_eq!(tokens[1].token, Eof);
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_comments() -> Result<(), LoxError> {
+        let mut scanner = Scanner::new("// leading\nvar x; // trailing \r\n//\n");
+        scanner.scan_tokens()?;
+        let got: Vec<_> = scanner
+            .comments()
+            .iter()
+            .map(|c| (c.line, c.text.as_str()))
+            .collect();
+        assert_eq!(got, vec![(1, "// leading"), (2, "// trailing"), (3, "//")]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_columns() -> Result<(), LoxError> {
+        let mut scanner = Scanner::new("var x = \"é\";\n  print x;");
+        let tokens = scanner.scan_tokens()?;
+        let got: Vec<_> = tokens.iter().map(|t| (t.line, t.column)).collect();
+        assert_eq!(
+            got,
+            vec![
+                (1, 1),
+                (1, 5),
+                (1, 7),
+                (1, 9),
+                (1, 12),
+                (2, 3),
+                (2, 9),
+                (2, 10),
+                (2, 11)
+            ]
+        );
+        // a string spanning lines starts where its opening quote is
+        let mut scanner = Scanner::new("\"a\nbc\" x \"\"\"\n\"\"\" y");
+        let tokens = scanner.scan_tokens()?;
+        let got: Vec<_> = tokens.iter().map(|t| (t.line, t.column)).collect();
+        assert_eq!(got, vec![(1, 1), (2, 5), (2, 7), (3, 5), (3, 6)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_partial() {
+        let mut scanner = Scanner::new("var # x = \"open");
+        let (tokens, errors) = scanner.scan_partial();
+        let got: Vec<_> = tokens.iter().map(|t| t.token.clone()).collect();
+        assert_eq!(got, vec![Var, Identifier, Equal, Eof]);
+        assert_eq!(errors.len(), 2);
+    }
+
     #[rstest::rstest]
     #[case(
         "var x = \"interrupted string ends here",
         "[line 1] Error: Unterminated string."
     )]
     #[case("\n\n#nofilter", "[line 3] Error: Unexpected character: #")]
+    #[case("\"a ${b", "[line 1] Error: Unterminated interpolation.")]
+    #[case("1.;", "[line 1] Error: Malformed number: 1.")]
+    #[case("0x;", "[line 1] Error: Malformed number: 0x")]
+    #[case("0b102", "[line 1] Error: Malformed number: 0b102")]
+    #[case("1e+", "[line 1] Error: Malformed number: 1e+")]
+    #[case("1__0", "[line 1] Error: Malformed number: 1__0")]
+    #[case("10_", "[line 1] Error: Malformed number: 10_")]
+    #[case("0x_1", "[line 1] Error: Malformed number: 0x_1")]
+    #[case("12ab", "[line 1] Error: Malformed number: 12ab")]
+    #[case("\"tab\\q\"", "[line 1] Error: Invalid escape sequence: \\q")]
+    #[case(
+        "\"\\u{110000}\"",
+        "[line 1] Error: Invalid unicode escape: \\u{110000"
+    )]
+    #[case("\"\\u{41\"", "[line 1] Error: Invalid unicode escape: \\u{41")]
+    #[case("\"\\u41\"", "[line 1] Error: Invalid unicode escape: \\u")]
+    #[case("\"\"\"\nraw\n\"\"", "[line 3] Error: Unterminated string.")]
+    #[case("\"a ${b} c", "[line 1] Error: Unterminated string.")]
     fn test_scan_types_error(#[case] input: &str, #[case] want: &str) {
         let mut scanner = Scanner::new(input);
         let err = scanner.scan_tokens().expect_err("should fail to scan");
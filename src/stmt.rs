@@ -9,14 +9,22 @@ pub struct FunDecl {
     pub line: usize,
     pub name: Token,
     pub parameters: Vec<Token>,
+    // the type annotation on each parameter, if any
+    pub parameter_types: Vec<Option<Token>>,
+    pub return_type: Option<Token>,
     pub body: Rc<StmtList>,
+    // a `class`-prefixed method, called on the class rather than an instance
+    pub is_static: bool,
+    // a method without a parameter list, called when its property is read
+    pub is_getter: bool,
 }
 
 #[derive(Debug)]
 pub enum Stmt {
     Expr(usize, Expr),
     Print(usize, Expr),
-    VarDecl(usize, Token, Option<Expr>),
+    // the name, its type annotation and its initializer
+    VarDecl(usize, Token, Option<Token>, Option<Expr>),
     FunDecl(FunDecl),
     Block(StmtList),
     IfThenElse {
@@ -31,24 +39,67 @@ pub enum Stmt {
         line: usize,
         name: Token,
         parent: Option<Expr>,
+        // the traits after `with`, whose methods the class takes on
+        traits: Vec<Expr>,
         methods: Vec<FunDecl>,
+        // the line of the closing '}'
+        end_line: usize,
     },
+    TraitDecl {
+        line: usize,
+        name: Token,
+        methods: Vec<FunDecl>,
+        end_line: usize,
+    },
+    // Stands in for a statement that failed to parse; only parse_partial
+    // hands these out.
+    Error(usize),
+}
+
+impl Stmt {
+    // The line the statement starts on; blocks don't have one of their own.
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            Stmt::Expr(line, _)
+            | Stmt::Print(line, _)
+            | Stmt::VarDecl(line, ..)
+            | Stmt::While(line, _, _)
+            | Stmt::Return(line, _)
+            | Stmt::IfThenElse { line, .. }
+            | Stmt::ClassDecl { line, .. }
+            | Stmt::TraitDecl { line, .. }
+            | Stmt::Error(line) => Some(*line),
+            Stmt::FunDecl(fun_decl) => Some(fun_decl.line),
+            Stmt::Block(_) => None,
+        }
+    }
 }
 
 impl fmt::Display for FunDecl {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "(defn {} '(", self.name.lexeme)?;
-        for (i, parameter) in self.parameters.iter().enumerate() {
-            if i > 0 {
-                write!(f, " ")?;
+        if self.is_static {
+            write!(f, "(static ")?;
+        }
+        if self.is_getter {
+            write!(f, "(defget {} {{", self.name.lexeme)?;
+        } else {
+            write!(f, "(defn {} '(", self.name.lexeme)?;
+            for (i, parameter) in self.parameters.iter().enumerate() {
+                if i > 0 {
+                    write!(f, " ")?;
+                }
+                write!(f, "{}", parameter.lexeme)?;
             }
-            write!(f, "{}", parameter.lexeme)?;
+            write!(f, ") {{")?;
         }
-        write!(f, ") {{")?;
         for stmt in self.body.into_iter() {
             write!(f, "{stmt} ")?;
         }
-        write!(f, "}})")
+        write!(f, "}})")?;
+        if self.is_static {
+            write!(f, ")")?;
+        }
+        Ok(())
     }
 }
 
@@ -57,7 +108,7 @@ impl fmt::Display for Stmt {
         match self {
             Stmt::Expr(_, expr) => write!(f, "expr({expr})"),
             Stmt::Print(_, expr) => write!(f, "print({expr})"),
-            Stmt::VarDecl(_, token, expr) => match expr {
+            Stmt::VarDecl(_, token, _, expr) => match expr {
                 None => write!(f, "var({})", token.lexeme),
                 Some(expr) => write!(f, "var({} = {expr})", token.lexeme),
             },
@@ -90,23 +141,47 @@ impl fmt::Display for Stmt {
                 line: _,
                 name,
                 parent,
+                traits,
                 methods,
+                ..
             } => {
                 write!(f, "(defclass {} ", name.lexeme)?;
                 if let Some(p) = parent {
                     write!(f, "(from {p}) ")?;
                 }
+                if !traits.is_empty() {
+                    write!(f, "(with")?;
+                    for t in traits {
+                        write!(f, " {t}")?;
+                    }
+                    write!(f, ") ")?;
+                }
+                for method in methods {
+                    write!(f, "{method} ")?;
+                }
+                write!(f, ")")
+            }
+            Stmt::TraitDecl { name, methods, .. } => {
+                write!(f, "(deftrait {} ", name.lexeme)?;
                 for method in methods {
                     write!(f, "{method} ")?;
                 }
                 write!(f, ")")
             }
+            Stmt::Error(_) => write!(f, "error"),
         }
     }
 }
 
 #[derive(Debug)]
-pub struct StmtList(pub Vec<Stmt>);
+pub struct StmtList {
+    pub stmts: Vec<Stmt>,
+    // the line of the closing '}', so the formatter keeps comments before it
+    // inside the block
+    pub end_line: usize,
+    // set on the blocks the parser builds when it desugars a `for` loop
+    pub from_for: bool,
+}
 
 impl fmt::Display for StmtList {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -122,6 +197,6 @@ impl<'a> IntoIterator for &'a StmtList {
     type IntoIter = slice::Iter<'a, Stmt>;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.0.iter()
+        self.stmts.iter()
     }
 }
@@ -1,5 +1,6 @@
 use crate::callable::LoxFunction;
 use crate::class::LoxClass;
+use crate::class::LoxTrait;
 use crate::environment::Env;
 use crate::error::RuntimeError;
 use crate::interpreter::Interpreter;
@@ -12,17 +13,30 @@ use std::rc::Rc;
 
 impl Interpreter {
     pub fn eval(&mut self, stmt: &Stmt) -> Result<(), RuntimeError> {
+        if let Some(line) = stmt.line() {
+            self.trace(line, stmt)?;
+        }
         match stmt {
             Stmt::Expr(line, expr) => {
                 self.eval_expr(*line, expr)?;
                 Ok(())
             }
             Stmt::Print(line, expr) => {
+                if !self.capabilities.stdout {
+                    return Err(RuntimeError::PermissionDenied {
+                        line: format!("{line}").into(),
+                        action: "print".into(),
+                    });
+                }
                 let v = self.eval_expr(*line, expr)?;
-                writeln!(self, "{v}").expect("writes should not fail");
+                let v = self.stringify(*line, v)?;
+                writeln!(self.stdout, "{v}").map_err(|err| RuntimeError::OutputError {
+                    line: format!("{line}").into(),
+                    msg: format!("{err}").into(),
+                })?;
                 Ok(())
             }
-            Stmt::VarDecl(line, token, expr) => {
+            Stmt::VarDecl(line, token, _, expr) => {
                 let value = match expr {
                     None => Value::VNil,
                     Some(expr) => self.eval_expr(*line, expr)?,
@@ -31,10 +45,12 @@ impl Interpreter {
                 Ok(())
             }
             Stmt::FunDecl(fun_decl) => {
+                self.track_object()?;
                 let f = LoxFunction {
                     definition: fun_decl.clone().into(),
                     closure: self.environment.clone(),
                     is_init: false,
+                    takes_this: false,
                 };
                 let callable = Value::Callable(Rc::new(f));
                 self.environment.define(&fun_decl.name.lexeme, callable);
@@ -44,7 +60,9 @@ impl Interpreter {
                 name,
                 methods,
                 parent,
+                traits,
                 line,
+                ..
             } => {
                 let parent_class = match parent {
                     None => None,
@@ -52,10 +70,41 @@ impl Interpreter {
                         let maybe_class = self.eval_expr(*line, p)?;
                         match maybe_class {
                             Value::Class(lc) => Some(lc),
-                            _ => panic!("not a class"),
+                            value => Err(RuntimeError::SuperclassNotClass {
+                                line: line.to_string().into(),
+                                value,
+                            })?,
                         }
                     }
                 };
+                let mut used = vec![];
+                // which trait each method came from, for those the class
+                // doesn't define itself
+                let mut from = HashMap::new();
+                for t in traits {
+                    let t = match self.eval_expr(*line, t)? {
+                        Value::Trait(t) => t,
+                        value => Err(RuntimeError::WithNotTrait {
+                            line: line.to_string().into(),
+                            value,
+                        })?,
+                    };
+                    for method in t.methods.keys() {
+                        if methods.iter().any(|m| m.name.lexeme == *method) {
+                            continue;
+                        }
+                        if let Some(first) = from.insert(method.clone(), t.name.clone()) {
+                            Err(RuntimeError::TraitConflict {
+                                line: line.to_string().into(),
+                                class: name.lexeme.clone(),
+                                method: method.clone(),
+                                first,
+                                second: t.name.clone(),
+                            })?
+                        }
+                    }
+                    used.push(t);
+                }
                 let environment = match parent_class {
                     None => self.environment.clone(),
                     Some(ref lc) => {
@@ -66,25 +115,54 @@ impl Interpreter {
                 };
 
                 let mut method_table = HashMap::default();
+                let mut class_methods = HashMap::default();
                 for method in methods {
                     let name = method.name.lexeme.to_owned();
                     let m = LoxFunction {
                         definition: method.clone().into(),
                         closure: environment.clone(),
-                        is_init: name == "init",
+                        is_init: name == "init" && !method.is_static && !method.is_getter,
+                        takes_this: false,
+                    };
+                    match method.is_static {
+                        true => class_methods.insert(name, m),
+                        false => method_table.insert(name, m),
                     };
-                    method_table.insert(name, m);
                 }
+                self.track_object()?;
                 let class = LoxClass {
                     name: name.lexeme.clone(),
-                    methods: method_table,
+                    methods: method_table.into(),
+                    class_methods,
                     parent: parent_class,
+                    traits: used,
                 };
                 let object = Value::Class(class.into());
                 self.environment.define(&name.lexeme, object);
 
                 Ok(())
             }
+            Stmt::TraitDecl { name, methods, .. } => {
+                let mut method_table = HashMap::default();
+                for method in methods {
+                    let name = method.name.lexeme.to_owned();
+                    let m = LoxFunction {
+                        definition: method.clone().into(),
+                        closure: self.environment.clone(),
+                        is_init: name == "init" && !method.is_getter,
+                        takes_this: false,
+                    };
+                    method_table.insert(name, m);
+                }
+                self.track_object()?;
+                let t = LoxTrait {
+                    name: name.lexeme.clone(),
+                    methods: method_table,
+                };
+                self.environment
+                    .define(&name.lexeme, Value::Trait(t.into()));
+                Ok(())
+            }
             Stmt::Block(stmts) => {
                 let mut alt_env = self.environment.push();
                 mem::swap(&mut alt_env, &mut self.environment);
@@ -113,6 +191,7 @@ impl Interpreter {
             }
             Stmt::While(line, expr, stmt) => {
                 while bool::from(self.eval_expr(*line, expr)?) {
+                    self.tick()?;
                     self.eval(stmt)?;
                 }
                 Ok(())
@@ -124,6 +203,9 @@ impl Interpreter {
                     value,
                 })
             }
+            Stmt::Error(line) => Err(RuntimeError::Unparsed {
+                line: format!("{line}").into(),
+            }),
         }
     }
 }
@@ -132,10 +214,10 @@ impl Interpreter {
 mod tests {
     use crate::error::LoxResult;
     use crate::interpreter::Interpreter;
+    use crate::output::SharedBuffer;
     use crate::parser::Parser;
     use crate::resolver::Resolver;
     use crate::scanner::Scanner;
-    use std::str::from_utf8;
     //use std::rc::Rc;
 
     fn str_eval(input: &str) -> LoxResult<String> {
@@ -146,13 +228,34 @@ mod tests {
 
         let mut resolver = Resolver::default();
 
-        let mut interpreter = Interpreter::default();
+        let output = SharedBuffer::default();
+        let mut interpreter = Interpreter {
+            stdout: Box::new(output.clone()),
+            ..Default::default()
+        };
         let resolutions = resolver.resolve(&stmts)?;
         interpreter.resolutions = resolutions;
         interpreter.interpret(&stmts)?;
-        Ok(from_utf8(&interpreter.buffer)
-            .expect("must parse output")
-            .into())
+        Ok(output.contents())
+    }
+
+    #[test]
+    fn test_eval_partial_parse() -> LoxResult<()> {
+        let mut scanner = Scanner::new("print 1;\nprint ;\nprint 2;");
+        let tokens = scanner.scan_tokens()?;
+        let (stmts, _) = Parser::new(&tokens).parse_partial();
+        let output = SharedBuffer::default();
+        let mut interpreter = Interpreter {
+            stdout: Box::new(output.clone()),
+            ..Default::default()
+        };
+        let err = interpreter.interpret(&stmts).expect_err("should not run");
+        assert_eq!(
+            err.to_string(),
+            "[line 2] Error: can't run a statement that failed to parse"
+        );
+        assert_eq!(output.contents(), "1\n");
+        Ok(())
     }
 
     #[rstest::rstest]
@@ -169,6 +272,10 @@ mod tests {
     #[case("if (nil) print 4;", "")]
     #[case("if (nil) print 4; else print 3;", "3\n")]
     #[case("var i = 0; while (i < 4) {i = i + 1; print i;}", "1\n2\n3\n4\n")]
+    #[case(
+        "trait A { m() {} }\ntrait B { m() {} }\nvar C = B;\nclass X with A, C {\n  m() {}\n}\nprint X;",
+        "X\n"
+    )]
     fn test_eval(#[case] input: &str, #[case] want_stdout: &'static str) -> LoxResult<()> {
         let got = str_eval(input)?;
 
@@ -179,10 +286,36 @@ mod tests {
     #[rstest::rstest]
     #[case(
         "print nil;\n 4 + \"lox\";\n 2 + \"oops\";",
-        "[line TODO] Error: type mismatch: 4 vs lox",
+        "[line 2] Error: operands of '+' must be two numbers or two strings, got number 4 and string \"lox\"",
         "nil\n"
     )]
-    #[case("x = 4;", "[line TODO] Error: undefined variable: 'x'", "")]
+    #[case("x = 4;", "[line 1] Error: undefined variable: 'x'", "")]
+    #[case(
+        "var A = 5;\nclass B < A {}",
+        "[line 2] Error: superclass must be a class, got 5",
+        ""
+    )]
+    #[case(
+        "print 1;\n5.x;",
+        "[line 2] Error: only instances have properties, got 5 for 'x'",
+        "1\n"
+    )]
+    #[case(
+        "nil\n.x = 1;",
+        "[line 2] Error: only instances have fields, got nil for 'x'",
+        ""
+    )]
+    #[case(
+        "class A {}\nclass B < A {\n  m() {\n    super.nope();\n  }\n}\nB().m();",
+        "[line 4] Error: undefined property: 'nope'",
+        ""
+    )]
+    // the resolver can't tell which trait C is
+    #[case(
+        "trait A { m() {} }\ntrait B { m() {} }\nvar C = B;\nclass X with A, C {}",
+        "[line 4] Error at 'X': method 'm' comes from both 'A' and 'B'",
+        ""
+    )]
     fn test_eval_error(
         #[case] input: &str,
         #[case] want: &str,
@@ -305,6 +438,33 @@ print count();
         Ok(())
     }
 
+    #[test]
+    fn test_lambda() -> LoxResult<()> {
+        let input = r#"
+fun apply(f, x) {
+    return f(x);
+}
+var n = 10;
+var add = (a, b) => a + b + n;
+print add(1, 2);
+print apply(fun (x) { return x * 2; }, 4);
+print apply((x) => () => x, "curried")();
+fun (x) { print x; }("called at once");
+print add;
+"#;
+        let got = str_eval(input)?;
+        assert_eq!(
+            got,
+            "13
+8
+curried
+called at once
+<fn lambda>
+"
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_lexical_scoping() -> LoxResult<()> {
         let input = r#"
@@ -425,7 +585,10 @@ method(); //
     fn test_invalid_this() -> LoxResult<()> {
         let input = "print this;";
         let got = str_eval(input).expect_err("should fail");
-        assert_eq!(format!("{got}"), "this outside of class: This \"this\"");
+        assert_eq!(
+            format!("{got}"),
+            "[line 1] Error at 'this': this outside of class"
+        );
         Ok(())
     }
 
@@ -458,7 +621,7 @@ class Foo {
         let got = str_eval(input).expect_err("should fail");
         assert_eq!(
             format!("{got}"),
-            "return outside of function: something else"
+            "[line 4] Error at 'return': can't return a value from an initializer"
         );
         Ok(())
     }
@@ -507,6 +670,169 @@ BostonCream().cook();
         Ok(())
     }
 
+    #[test]
+    fn test_class_methods_and_getters() -> LoxResult<()> {
+        let input = r#"
+class Shape {
+  class unit() {
+    return this(1);
+  }
+  class kind {
+    return "shape";
+  }
+  init(side) {
+    this.side = side;
+  }
+  area {
+    return this.side * this.side;
+  }
+}
+class Square < Shape {
+  class kind {
+    return "square, a " + super.kind;
+  }
+}
+print Shape.unit().area;
+print Square(3).area;
+print Square.kind;
+print Square.unit;
+"#;
+        let got = str_eval(input)?;
+        assert_eq!(
+            got,
+            "1
+9
+square, a shape
+<fn unit>
+"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_operator_overloading() -> LoxResult<()> {
+        let input = r#"
+class Vec {
+  init(x, y) {
+    this.x = x;
+    this.y = y;
+  }
+  __add__(other) {
+    return Vec(this.x + other.x, this.y + other.y);
+  }
+  __neg__() {
+    return Vec(-this.x, -this.y);
+  }
+  __lt__(other) {
+    return this.x < other.x;
+  }
+  __eq__(other) {
+    return this.x == other.x and this.y == other.y;
+  }
+  __str__() {
+    return "a vector";
+  }
+}
+var a = Vec(1, 2);
+print (a + Vec(3, 4)).y;
+print (-a).x;
+print a < Vec(2, 0);
+print a == Vec(1, 2);
+print a != Vec(1, 2);
+print a;
+"#;
+        let got = str_eval(input)?;
+        assert_eq!(
+            got,
+            "6
+-1
+true
+true
+false
+a vector
+"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_traits() -> LoxResult<()> {
+        let input = r#"
+trait Greets {
+  greet() {
+    return "hi, " + this.name();
+  }
+}
+trait Shouts {
+  shout() {
+    return this.greet() + "!";
+  }
+  name() {
+    return "trait";
+  }
+}
+class Base {
+  greet() {
+    return "base";
+  }
+  name() {
+    return "base";
+  }
+}
+class Person < Base with Greets, Shouts {
+  name() {
+    return "ada";
+  }
+  base() {
+    return super.greet();
+  }
+}
+var p = Person();
+print p.shout();
+print p.base();
+print p is Greets;
+print Base() is Greets;
+print Greets;
+"#;
+        let got = str_eval(input)?;
+        assert_eq!(
+            got,
+            "hi, ada!
+base
+true
+false
+Greets
+"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_interpolation() -> LoxResult<()> {
+        let input = r#"
+class P {
+  __str__() {
+    return "a P";
+  }
+}
+var n = 3;
+print "n=${n}, next=${n + 1}";
+print "${nil} ${true} ${P()} ${P}";
+print "nested ${"n is ${n}"}!";
+print "\${n}";
+"#;
+        let got = str_eval(input)?;
+        assert_eq!(
+            got,
+            "n=3, next=4
+nil true a P P
+nested n is 3!
+${n}
+"
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_super_stack() -> LoxResult<()> {
         let input = r#"
@@ -546,7 +872,10 @@ C().test();
 }
 "#;
         let got = str_eval(input).expect_err("should not work");
-        assert_eq!(format!("{got}"), "super outside of subclass");
+        assert_eq!(
+            format!("{got}"),
+            "[line 4] Error at 'super': super in a class without a superclass"
+        );
         Ok(())
     }
 }
@@ -0,0 +1,270 @@
+use crate::models::Token;
+use compact_str::CompactString;
+use std::collections::HashMap;
+use std::fmt;
+
+// What the resolver learned about names, kept around for editor tooling.
+// Scope 0 is the global scope; the rest are blocks, functions and classes.
+#[derive(Debug)]
+pub struct Symbols {
+    pub declarations: Vec<Declaration>,
+    pub references: Vec<Reference>,
+    pub scopes: Vec<Scope>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeclKind {
+    Variable,
+    Parameter,
+    Function,
+    Method,
+    Class,
+    Trait,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Declaration {
+    pub name: Token,
+    pub kind: DeclKind,
+    pub scope: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reference {
+    pub name: Token,
+    // index into declarations, None for natives and undefined globals
+    pub declaration: Option<usize>,
+}
+
+#[derive(Debug, Default)]
+pub struct Scope {
+    pub parent: Option<usize>,
+    // first and last line of anything declared or referenced inside
+    pub lines: Option<(usize, usize)>,
+    names: HashMap<CompactString, usize>,
+}
+
+impl fmt::Display for DeclKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = match self {
+            DeclKind::Variable => "variable",
+            DeclKind::Parameter => "parameter",
+            DeclKind::Function => "function",
+            DeclKind::Method => "method",
+            DeclKind::Class => "class",
+            DeclKind::Trait => "trait",
+        };
+        write!(f, "{text}")
+    }
+}
+
+impl Default for Symbols {
+    fn default() -> Self {
+        Self {
+            declarations: vec![],
+            references: vec![],
+            scopes: vec![Scope::default()],
+        }
+    }
+}
+
+impl Symbols {
+    pub fn push_scope(&mut self, parent: usize) -> usize {
+        self.scopes.push(Scope {
+            parent: Some(parent),
+            ..Default::default()
+        });
+        self.scopes.len() - 1
+    }
+
+    pub fn declare(&mut self, scope: usize, name: &Token, kind: DeclKind) {
+        self.declarations.push(Declaration {
+            name: name.clone(),
+            kind,
+            scope,
+        });
+        let index = self.declarations.len() - 1;
+        // globals may be redefined; references go to the first definition
+        self.scopes[scope]
+            .names
+            .entry(name.lexeme.clone())
+            .or_insert(index);
+        self.touch(scope, name.line);
+    }
+
+    // Records a use of a name, looking it up through the given scope chain.
+    // Globals are looked up later by link_globals, since functions may refer
+    // to globals declared after them.
+    pub fn reference(&mut self, scope: usize, name: &Token) {
+        let mut declaration = None;
+        let mut current = Some(scope);
+        while let Some(id) = current
+            && id != 0
+        {
+            if let Some(index) = self.scopes[id].names.get(&name.lexeme) {
+                declaration = Some(*index);
+                break;
+            }
+            current = self.scopes[id].parent;
+        }
+        self.references.push(Reference {
+            name: name.clone(),
+            declaration,
+        });
+        self.touch(scope, name.line);
+    }
+
+    pub fn link_globals(&mut self) {
+        for reference in self.references.iter_mut() {
+            if reference.declaration.is_none() {
+                reference.declaration = self.scopes[0].names.get(&reference.name.lexeme).copied();
+            }
+        }
+    }
+
+    fn touch(&mut self, scope: usize, line: usize) {
+        let mut current = Some(scope);
+        while let Some(id) = current {
+            let lines = &mut self.scopes[id].lines;
+            *lines = match *lines {
+                None => Some((line, line)),
+                Some((first, last)) => Some((first.min(line), last.max(line))),
+            };
+            current = self.scopes[id].parent;
+        }
+    }
+
+    // The declaration named by the token at a 1-based line and column, whether
+    // the token is the declaration itself or a reference to it.
+    pub fn declaration_at(&self, line: usize, column: usize) -> Option<usize> {
+        let covers = |token: &Token| {
+            token.line == line
+                && token.column <= column
+                && column <= token.column + token.lexeme.chars().count()
+        };
+        if let Some(index) = self.declarations.iter().position(|d| covers(&d.name)) {
+            return Some(index);
+        }
+        self.references
+            .iter()
+            .find(|r| covers(&r.name))
+            .and_then(|r| r.declaration)
+    }
+
+    pub fn references_to(&self, declaration: usize) -> impl Iterator<Item = &Token> {
+        self.references
+            .iter()
+            .filter(move |r| r.declaration == Some(declaration))
+            .map(|r| &r.name)
+    }
+
+    // Declarations in scope at a line: everything in the innermost scope
+    // covering the line and its parents, locals only once they are declared.
+    pub fn visible_at(&self, line: usize) -> Vec<&Declaration> {
+        let innermost = (1..self.scopes.len())
+            .filter(|id| {
+                matches!(self.scopes[*id].lines,
+                    Some((first, last)) if first <= line && line <= last)
+            })
+            .min_by_key(|id| {
+                let (first, last) = self.scopes[*id].lines.unwrap_or_default();
+                last - first
+            })
+            .unwrap_or(0);
+        let mut chain = vec![];
+        let mut current = Some(innermost);
+        while let Some(id) = current {
+            chain.push(id);
+            current = self.scopes[id].parent;
+        }
+        let mut visible: Vec<&Declaration> = vec![];
+        for id in chain {
+            for decl in self.declarations.iter().filter(|d| d.scope == id) {
+                if id != 0 && decl.name.line > line {
+                    continue;
+                }
+                if visible.iter().any(|v| v.name.lexeme == decl.name.lexeme) {
+                    continue;
+                }
+                visible.push(decl);
+            }
+        }
+        visible
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::LoxError;
+    use crate::parser::Parser;
+    use crate::resolver::Resolver;
+    use crate::scanner::Scanner;
+
+    fn symbols(input: &str) -> Result<Symbols, LoxError> {
+        let mut scanner = Scanner::new(input);
+        let tokens = scanner.scan_tokens()?;
+        let mut parser = Parser::new(&tokens);
+        let stmts = parser.parse()?;
+        let mut resolver = Resolver::default();
+        resolver.resolve(&stmts)?;
+        Ok(resolver.into_symbols())
+    }
+
+    #[test]
+    fn test_references() -> Result<(), LoxError> {
+        let symbols = symbols("fun f() { return g; }\nvar g = 1;\n{ var g = 2; print g; }")?;
+        let got: Vec<_> = symbols
+            .references
+            .iter()
+            .map(|r| {
+                let decl = r.declaration.map(|d| &symbols.declarations[d]);
+                (
+                    r.name.line,
+                    decl.map(|d| (d.name.line, d.name.column, d.kind)),
+                )
+            })
+            .collect();
+        // the global is found even though f is declared before it
+        assert_eq!(
+            got,
+            vec![
+                (1, Some((2, 5, DeclKind::Variable))),
+                (3, Some((3, 7, DeclKind::Variable))),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_declaration_at() -> Result<(), LoxError> {
+        let symbols = symbols("var abc = 1;\nprint abc + clock();")?;
+        assert_eq!(symbols.declaration_at(1, 5), Some(0));
+        assert_eq!(symbols.declaration_at(2, 7), Some(0));
+        assert_eq!(symbols.declaration_at(2, 10), Some(0));
+        assert_eq!(symbols.declaration_at(2, 14), None);
+        assert_eq!(symbols.declaration_at(2, 2), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_visible_at() -> Result<(), LoxError> {
+        let symbols = symbols("var a;\n{\n  var b;\n  var a;\n  print a;\n}\nvar c;")?;
+        let names = |line| -> Vec<_> {
+            symbols
+                .visible_at(line)
+                .iter()
+                .map(|d| (d.name.lexeme.to_string(), d.name.line))
+                .collect()
+        };
+        assert_eq!(
+            names(3),
+            vec![("b".into(), 3), ("a".into(), 1), ("c".into(), 7)]
+        );
+        assert_eq!(
+            names(5),
+            vec![("b".into(), 3), ("a".into(), 4), ("c".into(), 7)]
+        );
+        Ok(())
+    }
+}
@@ -14,6 +14,7 @@ pub enum TokenType {
     Minus,
     Plus,
     Semicolon,
+    Colon,
     Slash,
     Star,
 
@@ -26,10 +27,17 @@ pub enum TokenType {
     GreaterEqual,
     Less,
     LessEqual,
+    Arrow,
 
     // Literals
     Identifier,
     TString(CompactString),
+    // A string with `${...}` in it is split at each expression: into its
+    // start up to the first `${`, the text between one `}` and the next
+    // `${`, and its end after the last `}`.
+    InterpolationStart(CompactString),
+    InterpolationMid(CompactString),
+    InterpolationEnd(CompactString),
     TNumber(f64),
 
     // Keywords
@@ -40,19 +48,76 @@ pub enum TokenType {
     Fun,
     For,
     If,
+    Is,
     Nil,
     Or,
     Print,
     Return,
     Super,
     This,
+    Trait,
     True,
     Var,
     While,
+    With,
 
     Eof,
 }
 
+impl Display for TokenType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use TokenType::*;
+        let text = match self {
+            LeftParen => "'('",
+            RightParen => "')'",
+            LeftBrace => "'{'",
+            RightBrace => "'}'",
+            Comma => "','",
+            Dot => "'.'",
+            Minus => "'-'",
+            Plus => "'+'",
+            Semicolon => "';'",
+            Colon => "':'",
+            Slash => "'/'",
+            Star => "'*'",
+            Bang => "'!'",
+            BangEqual => "'!='",
+            Equal => "'='",
+            EqualEqual => "'=='",
+            Greater => "'>'",
+            GreaterEqual => "'>='",
+            Less => "'<'",
+            LessEqual => "'<='",
+            Arrow => "'=>'",
+            Identifier => "identifier",
+            TString(_) | InterpolationStart(_) => "string",
+            InterpolationMid(_) | InterpolationEnd(_) => "'}'",
+            TNumber(_) => "number",
+            And => "'and'",
+            Class => "'class'",
+            Else => "'else'",
+            False => "'false'",
+            Fun => "'fun'",
+            For => "'for'",
+            If => "'if'",
+            Is => "'is'",
+            Nil => "'nil'",
+            Or => "'or'",
+            Print => "'print'",
+            Return => "'return'",
+            Super => "'super'",
+            This => "'this'",
+            Trait => "'trait'",
+            True => "'true'",
+            Var => "'var'",
+            While => "'while'",
+            With => "'with'",
+            Eof => "end of file",
+        };
+        write!(f, "{text}")
+    }
+}
+
 // if this doesn't include the current offset in the line, can these refer to different tokens?
 // we should be able to ignore
 // or what about &Token or *Token, what if they are copied?
@@ -62,6 +127,8 @@ pub struct Token {
     pub token: TokenType,
     pub lexeme: CompactString,
     pub line: usize,
+    // 1-based, counted in chars
+    pub column: usize,
 }
 
 impl Display for Token {
@@ -69,3 +136,11 @@ impl Display for Token {
         write!(f, "{:?} {:?}", self.token, self.lexeme)
     }
 }
+
+// Comments are dropped from the token stream but kept as trivia so the
+// formatter can put them back.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    pub line: usize,
+    pub text: CompactString,
+}
@@ -1,12 +1,13 @@
 use crate::callable::LoxCallable;
 use crate::class::LoxClass;
 use crate::class::LoxInstance;
+use crate::class::LoxTrait;
 use crate::error::RuntimeError;
 use compact_str::CompactString;
-use std::convert::TryFrom;
 use std::fmt;
 use std::rc::Rc;
 
+use crate::token::Token;
 use crate::token::TokenType;
 
 type OpOutput = Result<Value, RuntimeError>;
@@ -20,6 +21,7 @@ pub enum Value {
     VString(CompactString),
     Callable(Rc<dyn LoxCallable>),
     Class(Rc<LoxClass>),
+    Trait(Rc<LoxTrait>),
     Object(Rc<LoxInstance>),
 }
 
@@ -35,23 +37,59 @@ impl fmt::Display for Value {
             Callable(func) => write!(f, "{func}"),
             Object(x) => write!(f, "{x}"),
             Class(c) => write!(f, "{c}"),
+            Trait(t) => write!(f, "{t}"),
         }
     }
 }
 
-impl TryFrom<Value> for f64 {
-    type Error = RuntimeError;
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            VNil => "nil",
+            Bool(_) => "boolean",
+            VNumber(_) => "number",
+            VString(_) => "string",
+            Callable(_) => "function",
+            Class(_) => "class",
+            Trait(_) => "trait",
+            Object(_) => "instance",
+        }
+    }
 
-    fn try_from(value: Value) -> Result<f64, Self::Error> {
-        if let VNumber(x) = value {
-            Ok(x)
-        } else {
-            Err(RuntimeError::TypeMismatch {
-                line: "TODO".into(),
-                lhs: value.into(),
-                rhs: VNumber(1197.9).into(),
-            })
+    // Orders two numbers or two strings for a comparison operator.
+    pub fn compare(&self, operator: &Token, other: &Value) -> OpOutput {
+        if !matches!(
+            (self, other),
+            (VNumber(_), VNumber(_)) | (VString(_), VString(_))
+        ) {
+            return Err(RuntimeError::TypeMismatch {
+                line: operator.line.to_string().into(),
+                operator: operator.lexeme.clone(),
+                expected: "two numbers or two strings",
+                operands: vec![self.clone(), other.clone()],
+            });
         }
+        Ok(Bool(match operator.token {
+            TokenType::Less => self < other,
+            TokenType::LessEqual => self <= other,
+            TokenType::Greater => self > other,
+            TokenType::GreaterEqual => self >= other,
+            // ok to panic -- we should never parse a different comparison
+            _ => panic!("invalid comparison '{}'", operator.lexeme),
+        }))
+    }
+}
+
+// Both operands of an arithmetic operator as numbers.
+fn numbers(operator: &str, lhs: Value, rhs: Value) -> Result<(f64, f64), RuntimeError> {
+    match (lhs, rhs) {
+        (VNumber(lhs), VNumber(rhs)) => Ok((lhs, rhs)),
+        (lhs, rhs) => Err(RuntimeError::TypeMismatch {
+            line: "TODO".into(),
+            operator: operator.into(),
+            expected: "numbers",
+            operands: vec![lhs, rhs],
+        }),
     }
 }
 
@@ -77,8 +115,9 @@ impl std::ops::Add for Value {
             (VString(lhs), VString(rhs)) => Ok(VString(lhs + &rhs)),
             (lhs, rhs) => Err(RuntimeError::TypeMismatch {
                 line: "TODO".into(),
-                lhs: lhs.into(),
-                rhs: rhs.into(),
+                operator: "+".into(),
+                expected: "two numbers or two strings",
+                operands: vec![lhs, rhs],
             }),
         }
     }
@@ -88,8 +127,7 @@ impl std::ops::Sub for Value {
     type Output = OpOutput;
 
     fn sub(self, other: Value) -> Self::Output {
-        let lhs = f64::try_from(self)?;
-        let rhs = f64::try_from(other)?;
+        let (lhs, rhs) = numbers("-", self, other)?;
         Ok(VNumber(lhs - rhs))
     }
 }
@@ -98,8 +136,7 @@ impl std::ops::Div for Value {
     type Output = OpOutput;
 
     fn div(self, other: Value) -> Self::Output {
-        let lhs = f64::try_from(self)?;
-        let rhs = f64::try_from(other)?;
+        let (lhs, rhs) = numbers("/", self, other)?;
         if rhs == 0.0 {
             Err(RuntimeError::ZeroDivError {
                 line: "TODO".into(),
@@ -114,8 +151,15 @@ impl std::ops::Neg for Value {
     type Output = OpOutput;
 
     fn neg(self) -> Self::Output {
-        let rhs = f64::try_from(self)?;
-        Ok(VNumber(-rhs))
+        match self {
+            VNumber(rhs) => Ok(VNumber(-rhs)),
+            value => Err(RuntimeError::TypeMismatch {
+                line: "TODO".into(),
+                operator: "-".into(),
+                expected: "number",
+                operands: vec![value],
+            }),
+        }
     }
 }
 
@@ -123,8 +167,7 @@ impl std::ops::Mul for Value {
     type Output = OpOutput;
 
     fn mul(self, other: Value) -> Self::Output {
-        let lhs = f64::try_from(self)?;
-        let rhs = f64::try_from(other)?;
+        let (lhs, rhs) = numbers("*", self, other)?;
         Ok(VNumber(lhs * rhs))
     }
 }
@@ -160,6 +203,11 @@ impl std::cmp::PartialEq for Value {
             (VString(lhs), VString(rhs)) => lhs == rhs,
             (Bool(lhs), Bool(rhs)) => lhs == rhs,
             (VNil, VNil) => true,
+            // everything else is the same only if it is the very same object
+            (Callable(lhs), Callable(rhs)) => std::ptr::addr_eq(Rc::as_ptr(lhs), Rc::as_ptr(rhs)),
+            (Class(lhs), Class(rhs)) => Rc::ptr_eq(lhs, rhs),
+            (Trait(lhs), Trait(rhs)) => Rc::ptr_eq(lhs, rhs),
+            (Object(lhs), Object(rhs)) => Rc::ptr_eq(lhs, rhs),
             _ => false,
         }
     }
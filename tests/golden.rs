@@ -0,0 +1,237 @@
+// Runs every Lox script under the test directories and checks what it does
+// against comments in the script itself, following the conventions of the
+// Crafting Interpreters test suite:
+//
+//   print 1 + 2;  // expect: 3
+//   -nil;         // expect runtime error: operand of '-' must be a number, got nil
+//   var 4;        // Error at '4': expected identifier for variable name
+//   // [line 7] Error at end: expected expression
+//
+// Output lines are matched in order. A runtime error must be reported for the
+// line it is written on, and compile errors are matched as a set; either way
+// nothing else may appear on stderr, and the exit code must be the one jlox
+// would use.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+
+struct Suite {
+    dir: &'static str,
+    // run with --jlox, so errors are reported in jlox's words
+    jlox: bool,
+}
+
+const SUITES: &[Suite] = &[
+    Suite {
+        dir: "testdata",
+        jlox: false,
+    },
+    Suite {
+        dir: "tests/lox",
+        jlox: false,
+    },
+    // The test/ directory of Crafting Interpreters
+    // (github.com/munificent/craftinginterpreters), in its layout. Left out
+    // are benchmark, expressions and scanning, which only clox runs, and
+    // limit, which tests clox's fixed-size tables and jlox skips too.
+    Suite {
+        dir: "tests/jlox",
+        jlox: true,
+    },
+];
+
+// Prints how long it took, and takes minutes without optimizations.
+const SKIP: &[&str] = &["testdata/fib_timer.lox"];
+
+// Where we knowingly differ from jlox. A script listed here must fail, so
+// the list is updated as the gaps are closed.
+const KNOWN_FAILURES: &[&str] = &[
+    // dividing by zero is an error rather than NaN or infinity
+    "tests/jlox/number/nan_equality.lox",
+    // a number can't end in a '.'
+    "tests/jlox/number/decimal_point_at_eof.lox",
+    "tests/jlox/number/trailing_dot.lox",
+    // natives print their name rather than "<native fn>"
+    "tests/jlox/function/print.lox",
+    // branches of an if may be declarations
+    "tests/jlox/if/class_in_else.lox",
+    "tests/jlox/if/class_in_then.lox",
+    "tests/jlox/if/fun_in_else.lox",
+    "tests/jlox/if/fun_in_then.lox",
+    "tests/jlox/if/var_in_else.lox",
+    "tests/jlox/if/var_in_then.lox",
+    // `fun` starts a lambda in an expression, so it wants a '(' next
+    "tests/jlox/for/fun_in_body.lox",
+    "tests/jlox/while/fun_in_body.lox",
+    // classes have properties (their class methods), so a missing one is
+    // undefined rather than an error for not being an instance
+    "tests/jlox/field/get_on_class.lox",
+    // the value of a property assignment is evaluated before the object
+    "tests/jlox/field/set_evaluation_order.lox",
+    // too many arguments or parameters are reported at the token after the
+    // list rather than at the one past the limit
+    "tests/jlox/function/too_many_arguments.lox",
+    "tests/jlox/function/too_many_parameters.lox",
+    "tests/jlox/method/too_many_arguments.lox",
+    "tests/jlox/method/too_many_parameters.lox",
+    // we don't parse after scan errors, so only the first is reported
+    "tests/jlox/unexpected_character.lox",
+];
+
+const COMPILE_ERROR: i32 = 65;
+const RUNTIME_ERROR: i32 = 70;
+
+#[derive(Debug, Default)]
+struct Expectations {
+    stdout: Vec<String>,
+    compile_errors: BTreeSet<String>,
+    // the message and the line it is reported on
+    runtime_error: Option<(String, usize)>,
+}
+
+fn expectations(src: &str) -> Expectations {
+    let mut expect = Expectations::default();
+    for (i, line) in src.lines().enumerate() {
+        let line_number = i + 1;
+        if let Some((_, text)) = line.split_once("// expect: ") {
+            expect.stdout.push(text.to_owned());
+        } else if let Some((_, msg)) = line.split_once("// expect runtime error: ") {
+            expect.runtime_error = Some((msg.to_owned(), line_number));
+        } else if let Some((_, error)) = line.split_once("// Error") {
+            expect
+                .compile_errors
+                .insert(format!("[line {line_number}] Error{error}"));
+        } else if let Some((_, error)) = line
+            .split_once("// [java line ")
+            .or_else(|| line.split_once("// [line "))
+        {
+            expect.compile_errors.insert(format!("[line {error}"));
+        }
+    }
+    expect
+}
+
+// Lines that differ, position by position.
+fn diff(want: &[String], got: &[&str]) -> Vec<String> {
+    let mut out = vec![];
+    for i in 0..want.len().max(got.len()) {
+        let (want, got) = (want.get(i).map(String::as_str), got.get(i).copied());
+        if want == got {
+            continue;
+        }
+        out.push(format!("  line {}:", i + 1));
+        if let Some(want) = want {
+            out.push(format!("    - {want}"));
+        }
+        if let Some(got) = got {
+            out.push(format!("    + {got}"));
+        }
+    }
+    out
+}
+
+// Everything wrong with one script's run.
+fn check(path: &Path, jlox: bool) -> Vec<String> {
+    let src = fs::read_to_string(path).expect("must read script");
+    let expect = expectations(&src);
+    let mut command = Command::new(env!("CARGO_BIN_EXE_rlox1"));
+    // a panic should fail the script, not fill the report with a backtrace
+    command.env("RUST_BACKTRACE", "0");
+    if jlox {
+        command.arg("--jlox");
+    }
+    let output = command.arg(path).output().expect("must run rlox1");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    let mut problems = vec![];
+    let got: Vec<_> = stdout.lines().collect();
+    let stdout_diff = diff(&expect.stdout, &got);
+    if !stdout_diff.is_empty() {
+        problems.push("stdout differs:".to_owned());
+        problems.extend(stdout_diff);
+    }
+
+    let (want_stderr, want_code) = match &expect.runtime_error {
+        Some((msg, line)) if jlox => (vec![msg.clone(), format!("[line {line}]")], RUNTIME_ERROR),
+        Some((msg, line)) => (vec![format!("[line {line}] Error: {msg}")], RUNTIME_ERROR),
+        None if !expect.compile_errors.is_empty() => (
+            expect.compile_errors.iter().cloned().collect(),
+            COMPILE_ERROR,
+        ),
+        None => (vec![], 0),
+    };
+    let mut got: Vec<_> = stderr.lines().collect();
+    if expect.runtime_error.is_none() {
+        // compile errors may come out in any order
+        got.sort();
+    }
+    let stderr_diff = diff(&want_stderr, &got);
+    if !stderr_diff.is_empty() {
+        problems.push("stderr differs:".to_owned());
+        problems.extend(stderr_diff);
+    }
+
+    let code = output.status.code();
+    if code != Some(want_code) {
+        problems.push(format!("exit code: want {want_code}, got {code:?}"));
+    }
+    problems
+}
+
+fn scripts(dir: &Path, out: &mut Vec<PathBuf>) {
+    let entries = fs::read_dir(dir).expect("must read test directory");
+    for entry in entries {
+        let path = entry.expect("must read directory entry").path();
+        if path.is_dir() {
+            scripts(&path, out);
+        } else if path.extension().is_some_and(|ext| ext == "lox") {
+            out.push(path);
+        }
+    }
+}
+
+#[test]
+fn golden() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut failures = vec![];
+    let mut total = 0;
+    for suite in SUITES {
+        let mut paths = vec![];
+        scripts(&root.join(suite.dir), &mut paths);
+        paths.sort();
+        let (mut passed, mut ran) = (0, 0);
+        for path in &paths {
+            let name = path
+                .strip_prefix(root)
+                .expect("scripts are under the root")
+                .to_string_lossy()
+                .into_owned();
+            if SKIP.contains(&name.as_str()) {
+                continue;
+            }
+            ran += 1;
+            let problems = check(path, suite.jlox);
+            let known = KNOWN_FAILURES.contains(&name.as_str());
+            match (problems.is_empty(), known) {
+                (true, false) => passed += 1,
+                (false, true) => {}
+                (true, true) => failures.push(format!(
+                    "{name}\n  passes now; remove it from KNOWN_FAILURES"
+                )),
+                (false, false) => failures.push(format!("{name}\n{}", problems.join("\n"))),
+            }
+        }
+        println!("{}: {passed} of {ran} passed", suite.dir);
+        total += ran;
+    }
+    assert!(
+        failures.is_empty(),
+        "{} of {total} scripts failed:\n\n{}",
+        failures.len(),
+        failures.join("\n\n")
+    );
+}